@@ -0,0 +1,219 @@
+use std::{collections::HashMap, slice::from_ref};
+
+use ash::{prelude::VkResult, vk};
+
+use crate::descriptor_sets::{pool_flags, pool_sizes_for_n, RawDescriptorSetInfo};
+
+/// Allocates sets of a single layout from pools created as they fill up.
+///
+/// Sets are either all released at once by `reset`, typically once per frame,
+/// or one by one with `free` for long-lived sets when created with
+/// `free_sets`.
+#[derive(Debug)]
+pub struct DescriptorAllocator {
+    layout: vk::DescriptorSetLayout,
+    sets_per_pool: u32,
+    pool_sizes: Vec<vk::DescriptorPoolSize>,
+    pool_flags: vk::DescriptorPoolCreateFlags,
+    /// Pools allocated from since the last reset, the last one is current.
+    used_pools: Vec<vk::DescriptorPool>,
+    /// Reset pools, reused before creating new ones.
+    free_pools: Vec<vk::DescriptorPool>,
+    /// Pool of every set, only with `free_sets`.
+    set_pools: Option<HashMap<vk::DescriptorSet, vk::DescriptorPool>>,
+}
+
+impl DescriptorAllocator {
+    /// `layout` must have been created from `F`, each pool holds
+    /// `sets_per_pool` sets.
+    pub fn new<F: RawDescriptorSetInfo + ?Sized>(
+        layout: vk::DescriptorSetLayout, sets_per_pool: u32, free_sets: bool,
+    ) -> Self {
+        let sets_per_pool = sets_per_pool.max(1);
+        let mut pool_flags = pool_flags::<F>();
+        if free_sets {
+            pool_flags |= vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET;
+        }
+
+        Self {
+            layout,
+            sets_per_pool,
+            pool_sizes: pool_sizes_for_n::<F>(sets_per_pool),
+            pool_flags,
+            used_pools: Vec::new(),
+            free_pools: Vec::new(),
+            set_pools: free_sets.then(HashMap::new),
+        }
+    }
+
+    #[inline]
+    pub unsafe fn allocate_one(&mut self, device: &ash::Device) -> VkResult<vk::DescriptorSet> {
+        Ok(self.allocate(device, 1)?[0])
+    }
+
+    /// On error, the sets of the batch already allocated are only released by
+    /// `reset`.
+    #[inline]
+    pub unsafe fn allocate(
+        &mut self, device: &ash::Device, count: usize,
+    ) -> VkResult<Vec<vk::DescriptorSet>> {
+        self.allocate_sets(device, count, None)
+    }
+
+    /// For layouts whose last binding has a variable descriptor count, every
+    /// set gets `variable_count` descriptors in that binding.
+    #[inline]
+    pub unsafe fn allocate_with_variable_count(
+        &mut self, device: &ash::Device, count: usize, variable_count: u32,
+    ) -> VkResult<Vec<vk::DescriptorSet>> {
+        self.allocate_sets(device, count, Some(variable_count))
+    }
+
+    unsafe fn allocate_sets(
+        &mut self, device: &ash::Device, count: usize, variable_count: Option<u32>,
+    ) -> VkResult<Vec<vk::DescriptorSet>> {
+        let mut sets = Vec::with_capacity(count);
+        // A batch never fits in a fresh pool when it's larger than a pool
+        for chunk_len in chunk_lens(count, self.sets_per_pool as usize) {
+            let layouts = vec![self.layout; chunk_len];
+            let variable_counts = vec![variable_count.unwrap_or(0); chunk_len];
+
+            let mut fresh_pool = false;
+            if self.used_pools.is_empty() {
+                self.next_pool(device)?;
+                fresh_pool = true;
+            }
+
+            let chunk = loop {
+                let pool = *self.used_pools.last().unwrap();
+                match allocate_from(
+                    device,
+                    pool,
+                    &layouts,
+                    variable_count.map(|_| &*variable_counts),
+                ) {
+                    Ok(chunk) => break chunk,
+                    // The current pool is full, retry once with a new one
+                    Err(
+                        vk::Result::ERROR_OUT_OF_POOL_MEMORY | vk::Result::ERROR_FRAGMENTED_POOL,
+                    ) if !fresh_pool => {
+                        self.next_pool(device)?;
+                        fresh_pool = true;
+                    }
+                    Err(e) => return Err(e),
+                }
+            };
+
+            if let Some(set_pools) = self.set_pools.as_mut() {
+                let pool = *self.used_pools.last().unwrap();
+                set_pools.extend(chunk.iter().map(|&set| (set, pool)));
+            }
+            sets.extend(chunk);
+        }
+
+        Ok(sets)
+    }
+
+    /// The sets must not be in use anymore. Only for allocators created with
+    /// `free_sets`, otherwise this does nothing and the sets are released by
+    /// `reset`.
+    pub unsafe fn free(
+        &mut self, device: &ash::Device, sets: &[vk::DescriptorSet],
+    ) -> VkResult<()> {
+        debug_assert!(
+            self.set_pools.is_some(),
+            "DescriptorAllocator::free needs an allocator created with `free_sets`"
+        );
+        let Some(set_pools) = self.set_pools.as_mut() else {
+            return Ok(());
+        };
+
+        for &set in sets {
+            if let Some(pool) = set_pools.remove(&set) {
+                device.free_descriptor_sets(pool, from_ref(&set))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Releases every set at once, none of them may be in use anymore. Pools
+    /// are kept for the next allocations.
+    pub unsafe fn reset(&mut self, device: &ash::Device) -> VkResult<()> {
+        for pool in self.used_pools.drain(..) {
+            device.reset_descriptor_pool(pool, vk::DescriptorPoolResetFlags::empty())?;
+            self.free_pools.push(pool);
+        }
+        if let Some(set_pools) = self.set_pools.as_mut() {
+            set_pools.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Destroys the pools, which releases every set. The layout is owned by
+    /// the caller.
+    pub unsafe fn destroy(&mut self, device: &ash::Device) {
+        for pool in self.used_pools.drain(..).chain(self.free_pools.drain(..)) {
+            device.destroy_descriptor_pool(pool, None);
+        }
+        if let Some(set_pools) = self.set_pools.as_mut() {
+            set_pools.clear();
+        }
+    }
+
+    /// Makes a reset or a new pool current.
+    unsafe fn next_pool(&mut self, device: &ash::Device) -> VkResult<()> {
+        let pool = match self.free_pools.pop() {
+            Some(pool) => pool,
+            None => device.create_descriptor_pool(
+                &vk::DescriptorPoolCreateInfo::builder()
+                    .flags(self.pool_flags)
+                    .max_sets(self.sets_per_pool)
+                    .pool_sizes(&self.pool_sizes),
+                None,
+            )?,
+        };
+
+        self.used_pools.push(pool);
+        Ok(())
+    }
+}
+
+unsafe fn allocate_from(
+    device: &ash::Device, pool: vk::DescriptorPool, layouts: &[vk::DescriptorSetLayout],
+    variable_counts: Option<&[u32]>,
+) -> VkResult<Vec<vk::DescriptorSet>> {
+    let mut variable_count_info = vk::DescriptorSetVariableDescriptorCountAllocateInfo::builder()
+        .descriptor_counts(variable_counts.unwrap_or_default());
+
+    let mut allocate_info = vk::DescriptorSetAllocateInfo::builder()
+        .descriptor_pool(pool)
+        .set_layouts(layouts);
+    if variable_counts.is_some() {
+        allocate_info = allocate_info.push_next(&mut variable_count_info);
+    }
+
+    device.allocate_descriptor_sets(&allocate_info)
+}
+
+/// Splits `count` in chunks of at most `max` elements.
+fn chunk_lens(count: usize, max: usize) -> impl Iterator<Item = usize> {
+    (0..count)
+        .step_by(max)
+        .map(move |start| max.min(count - start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_of_a_batch() {
+        assert_eq!(chunk_lens(0, 4).collect::<Vec<_>>(), []);
+        assert_eq!(chunk_lens(3, 4).collect::<Vec<_>>(), [3]);
+        assert_eq!(chunk_lens(8, 4).collect::<Vec<_>>(), [4, 4]);
+        assert_eq!(chunk_lens(10, 4).collect::<Vec<_>>(), [4, 4, 2]);
+        assert_eq!(chunk_lens(2, 1).collect::<Vec<_>>(), [1, 1]);
+    }
+}
@@ -1,8 +1,10 @@
-use std::{borrow::Cow, slice::from_ref};
+use std::{borrow::Cow, error::Error, fmt, slice::from_ref};
 
 use ash::{prelude::VkResult, vk};
 
-fn pool_sizes_for_n<F: RawDescriptorSetInfo + ?Sized>(
+use crate::samplers::{with_immutable_samplers, SamplerCache};
+
+pub(crate) fn pool_sizes_for_n<F: RawDescriptorSetInfo + ?Sized>(
     max_sets: u32,
 ) -> Vec<vk::DescriptorPoolSize> {
     F::POOL_SIZES_FOR_ONE
@@ -15,15 +17,64 @@ fn pool_sizes_for_n<F: RawDescriptorSetInfo + ?Sized>(
         .collect()
 }
 
+/// Update after bind layouts can only be allocated from such pools.
+pub(crate) fn pool_flags<F: RawDescriptorSetInfo + ?Sized>() -> vk::DescriptorPoolCreateFlags {
+    if F::LAYOUT_CREATE_INFO
+        .flags
+        .contains(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+    {
+        vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND
+    } else {
+        vk::DescriptorPoolCreateFlags::empty()
+    }
+}
+
 pub unsafe trait RawDescriptorSetInfo {
     const LAYOUT_BINDINGS_CREATE_INFO: &'static [vk::DescriptorSetLayoutBinding];
     const LAYOUT_CREATE_INFO: vk::DescriptorSetLayoutCreateInfo;
+    /// Flags of each binding in `LAYOUT_BINDINGS_CREATE_INFO` order, empty
+    /// when no binding has flags. Chained to `LAYOUT_CREATE_INFO` by
+    /// `create_layout`.
+    const BINDING_FLAGS: &'static [vk::DescriptorBindingFlags] = &[];
+    /// Patched in the bindings by `create_layout_with_samplers`.
+    const IMMUTABLE_SAMPLERS: &'static [ImmutableSamplers] = &[];
 
     const POOL_SIZES_FOR_ONE: &'static [vk::DescriptorPoolSize];
 
+    /// Fails to compile for sets with immutable samplers, which need
+    /// `create_layout_with_samplers`.
     #[inline]
     unsafe fn create_layout(device: &ash::Device) -> VkResult<vk::DescriptorSetLayout> {
-        device.create_descriptor_set_layout(&Self::LAYOUT_CREATE_INFO, None)
+        const {
+            assert!(
+                Self::IMMUTABLE_SAMPLERS.is_empty(),
+                "Set with immutable samplers, use `create_layout_with_samplers`"
+            )
+        };
+        Self::create_layout_with_samplers(device, &mut SamplerCache::default())
+    }
+
+    /// The immutable samplers are taken from `samplers`, which must outlive
+    /// the layout.
+    unsafe fn create_layout_with_samplers(
+        device: &ash::Device, samplers: &mut SamplerCache,
+    ) -> VkResult<vk::DescriptorSetLayout> {
+        let (bindings, _immutable_samplers) = with_immutable_samplers(
+            device,
+            samplers,
+            Self::LAYOUT_BINDINGS_CREATE_INFO,
+            Self::IMMUTABLE_SAMPLERS,
+        )?;
+
+        let mut create_info = Self::LAYOUT_CREATE_INFO;
+        create_info.p_bindings = bindings.as_ptr();
+        let binding_flags = vk::DescriptorSetLayoutBindingFlagsCreateInfo::builder()
+            .binding_flags(Self::BINDING_FLAGS);
+        if !Self::BINDING_FLAGS.is_empty() {
+            create_info.p_next = &*binding_flags as *const _ as _;
+        }
+
+        device.create_descriptor_set_layout(&create_info, None)
     }
 
     unsafe fn create_pool_for_set(
@@ -38,6 +89,7 @@ pub unsafe trait RawDescriptorSetInfo {
 
         device.create_descriptor_pool(
             &vk::DescriptorPoolCreateInfo::builder()
+                .flags(pool_flags::<Self>())
                 .max_sets(max_sets)
                 .pool_sizes(sizes.as_ref()),
             None,
@@ -53,4 +105,89 @@ pub unsafe trait RawDescriptorSetInfo {
                 .set_layouts(from_ref(&layout)),
         )?[0])
     }
+
+    /// For layouts whose last binding has a variable descriptor count,
+    /// `variable_count` is at most the count of that binding in the layout.
+    unsafe fn allocate_one_set_with_variable_count(
+        device: &ash::Device, pool: vk::DescriptorPool, layout: vk::DescriptorSetLayout,
+        variable_count: u32,
+    ) -> VkResult<vk::DescriptorSet> {
+        let mut variable_count_info =
+            vk::DescriptorSetVariableDescriptorCountAllocateInfo::builder()
+                .descriptor_counts(from_ref(&variable_count));
+
+        Ok(device.allocate_descriptor_sets(
+            &vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(pool)
+                .set_layouts(from_ref(&layout))
+                .push_next(&mut variable_count_info),
+        )?[0])
+    }
 }
+
+/// Samplers baked in a binding of a layout, created from their description
+/// when the layout is.
+#[derive(Debug, Clone, Copy)]
+pub struct ImmutableSamplers {
+    pub binding: u32,
+    /// One per descriptor of the binding.
+    pub create_infos: &'static [vk::SamplerCreateInfo],
+}
+
+/// Buffer bound to a uniform or storage buffer binding by a generated
+/// descriptor set writer.
+pub trait IntoDescriptorBufferInfo {
+    fn into_descriptor_buffer_info(self) -> vk::DescriptorBufferInfo;
+}
+
+/// The whole buffer.
+impl IntoDescriptorBufferInfo for vk::Buffer {
+    #[inline]
+    fn into_descriptor_buffer_info(self) -> vk::DescriptorBufferInfo {
+        vk::DescriptorBufferInfo {
+            buffer: self,
+            offset: 0,
+            range: vk::WHOLE_SIZE,
+        }
+    }
+}
+
+/// `(buffer, offset, range)`
+impl IntoDescriptorBufferInfo for (vk::Buffer, vk::DeviceSize, vk::DeviceSize) {
+    #[inline]
+    fn into_descriptor_buffer_info(self) -> vk::DescriptorBufferInfo {
+        let (buffer, offset, range) = self;
+        vk::DescriptorBufferInfo {
+            buffer,
+            offset,
+            range,
+        }
+    }
+}
+
+impl IntoDescriptorBufferInfo for vk::DescriptorBufferInfo {
+    #[inline]
+    fn into_descriptor_buffer_info(self) -> vk::DescriptorBufferInfo {
+        self
+    }
+}
+
+/// Returned by the `write` method of a generated descriptor set writer when a
+/// binding was not given a resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsetBinding {
+    pub binding: u32,
+    pub name: &'static str,
+}
+
+impl fmt::Display for UnsetBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "binding {} (`{}`) of the descriptor set was not set",
+            self.binding, self.name
+        )
+    }
+}
+
+impl Error for UnsetBinding {}
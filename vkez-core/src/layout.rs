@@ -0,0 +1,49 @@
+use std::mem;
+
+use ash::vk;
+
+/// `T` followed by `N` bytes of padding, for array elements whose stride is
+/// larger than their size, like `float` arrays in std140 blocks.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Padded<T, const N: usize> {
+    pub value: T,
+    pub padding: [u8; N],
+}
+
+impl<T, const N: usize> Padded<T, N> {
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        Self {
+            value,
+            padding: [0; N],
+        }
+    }
+}
+
+impl<T: Default, const N: usize> Default for Padded<T, N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T, const N: usize> From<T> for Padded<T, N> {
+    #[inline]
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Buffer block ending with a runtime-sized array, like `float data[]`. `Self`
+/// is the part before the array, and the elements start right after it.
+pub unsafe trait RuntimeArrayBlock: Copy {
+    /// Padded up to the array stride.
+    type Element: Copy;
+
+    /// Size of a buffer holding `len` elements.
+    #[inline]
+    fn buffer_size(len: usize) -> vk::DeviceSize {
+        (mem::size_of::<Self>() + len * mem::size_of::<Self::Element>()) as vk::DeviceSize
+    }
+}
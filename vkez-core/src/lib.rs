@@ -2,5 +2,10 @@ pub use ash;
 pub use tracing;
 pub use vk_mem;
 
+pub mod descriptor_allocator;
 pub mod descriptor_sets;
+pub mod layout;
+pub mod pipeline_layout;
+pub mod samplers;
 pub mod shaders;
+pub mod vertex;
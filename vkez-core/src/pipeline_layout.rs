@@ -0,0 +1,126 @@
+use ash::{prelude::VkResult, vk};
+
+use crate::{
+    descriptor_sets::ImmutableSamplers,
+    samplers::{with_immutable_samplers, SamplerCache},
+};
+
+pub unsafe trait RawPipelineLayoutInfo {
+    /// Bindings of every set in set index order, sets that no shader uses are
+    /// empty.
+    const SET_LAYOUT_BINDINGS: &'static [&'static [vk::DescriptorSetLayoutBinding]];
+    /// Flags of the bindings of every set, empty for sets without flags and
+    /// when no set has any.
+    const SET_BINDING_FLAGS: &'static [&'static [vk::DescriptorBindingFlags]] = &[];
+    /// Immutable samplers of every set, empty when no set has any.
+    const SET_IMMUTABLE_SAMPLERS: &'static [&'static [ImmutableSamplers]] = &[];
+    /// At most one range per stage.
+    const PUSH_CONSTANT_RANGES: &'static [vk::PushConstantRange];
+
+    /// Layouts of every set in set index order. Fails to compile when a set
+    /// has immutable samplers, which need `create_set_layouts_with_samplers`.
+    #[inline]
+    unsafe fn create_set_layouts(device: &ash::Device) -> VkResult<Vec<vk::DescriptorSetLayout>> {
+        const {
+            assert!(
+                Self::SET_IMMUTABLE_SAMPLERS.is_empty(),
+                "Sets with immutable samplers, use `create_set_layouts_with_samplers`"
+            )
+        };
+        Self::create_set_layouts_with_samplers(device, &mut SamplerCache::default())
+    }
+
+    /// Layouts of every set in set index order, the immutable samplers are
+    /// taken from `samplers` which must outlive them.
+    unsafe fn create_set_layouts_with_samplers(
+        device: &ash::Device, samplers: &mut SamplerCache,
+    ) -> VkResult<Vec<vk::DescriptorSetLayout>> {
+        let mut set_layouts = Vec::with_capacity(Self::SET_LAYOUT_BINDINGS.len());
+        for (set, &bindings) in Self::SET_LAYOUT_BINDINGS.iter().enumerate() {
+            let immutable_samplers = Self::SET_IMMUTABLE_SAMPLERS
+                .get(set)
+                .copied()
+                .unwrap_or_default();
+            let (bindings, _immutable_samplers) =
+                match with_immutable_samplers(device, samplers, bindings, immutable_samplers) {
+                    Ok(bindings) => bindings,
+                    Err(e) => {
+                        destroy_set_layouts(device, &set_layouts);
+                        return Err(e);
+                    }
+                };
+
+            let binding_flags = Self::SET_BINDING_FLAGS
+                .get(set)
+                .copied()
+                .unwrap_or_default();
+            let mut binding_flags_info = vk::DescriptorSetLayoutBindingFlagsCreateInfo::builder()
+                .binding_flags(binding_flags);
+
+            let mut create_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+            if !binding_flags.is_empty() {
+                create_info = create_info.push_next(&mut binding_flags_info);
+            }
+            if binding_flags
+                .iter()
+                .any(|flags| flags.contains(vk::DescriptorBindingFlags::UPDATE_AFTER_BIND))
+            {
+                create_info =
+                    create_info.flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL);
+            }
+
+            match device.create_descriptor_set_layout(&create_info, None) {
+                Ok(set_layout) => set_layouts.push(set_layout),
+                Err(e) => {
+                    destroy_set_layouts(device, &set_layouts);
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(set_layouts)
+    }
+
+    /// The set layouts are returned along the pipeline layout, they are owned
+    /// by the caller. Fails to compile when a set has immutable samplers, which
+    /// need `create_pipeline_layout_with_samplers`.
+    #[inline]
+    unsafe fn create_pipeline_layout(
+        device: &ash::Device,
+    ) -> VkResult<(vk::PipelineLayout, Vec<vk::DescriptorSetLayout>)> {
+        const {
+            assert!(
+                Self::SET_IMMUTABLE_SAMPLERS.is_empty(),
+                "Sets with immutable samplers, use `create_pipeline_layout_with_samplers`"
+            )
+        };
+        Self::create_pipeline_layout_with_samplers(device, &mut SamplerCache::default())
+    }
+
+    /// Like `create_pipeline_layout`, the immutable samplers are taken from
+    /// `samplers` which must outlive the set layouts.
+    unsafe fn create_pipeline_layout_with_samplers(
+        device: &ash::Device, samplers: &mut SamplerCache,
+    ) -> VkResult<(vk::PipelineLayout, Vec<vk::DescriptorSetLayout>)> {
+        let set_layouts = Self::create_set_layouts_with_samplers(device, samplers)?;
+
+        match device.create_pipeline_layout(
+            &vk::PipelineLayoutCreateInfo::builder()
+                .set_layouts(&set_layouts)
+                .push_constant_ranges(Self::PUSH_CONSTANT_RANGES),
+            None,
+        ) {
+            Ok(pipeline_layout) => Ok((pipeline_layout, set_layouts)),
+            Err(e) => {
+                destroy_set_layouts(device, &set_layouts);
+                Err(e)
+            }
+        }
+    }
+}
+
+unsafe fn destroy_set_layouts(device: &ash::Device, set_layouts: &[vk::DescriptorSetLayout]) {
+    for &set_layout in set_layouts {
+        device.destroy_descriptor_set_layout(set_layout, None);
+    }
+}
@@ -0,0 +1,99 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use ash::{prelude::VkResult, vk};
+
+use crate::descriptor_sets::ImmutableSamplers;
+
+/// Samplers shared by every identical create info, like the immutable samplers
+/// of the descriptor set layouts. They live until `destroy` is called.
+#[derive(Debug, Default)]
+pub struct SamplerCache {
+    samplers: HashMap<SamplerKey, vk::Sampler>,
+}
+
+impl SamplerCache {
+    /// `p_next` isn't part of the key, create infos with extension structures
+    /// aren't supported.
+    pub unsafe fn get_or_create(
+        &mut self, device: &ash::Device, create_info: &vk::SamplerCreateInfo,
+    ) -> VkResult<vk::Sampler> {
+        let key = SamplerKey::new(create_info);
+        if let Some(&sampler) = self.samplers.get(&key) {
+            return Ok(sampler);
+        }
+
+        let sampler = device.create_sampler(create_info, None)?;
+        self.samplers.insert(key, sampler);
+        Ok(sampler)
+    }
+
+    pub unsafe fn destroy(&mut self, device: &ash::Device) {
+        for (_, sampler) in self.samplers.drain() {
+            device.destroy_sampler(sampler, None);
+        }
+    }
+}
+
+/// Every field of `vk::SamplerCreateInfo` but `s_type` and `p_next`, floats as
+/// bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct SamplerKey([u32; 16]);
+
+impl SamplerKey {
+    fn new(info: &vk::SamplerCreateInfo) -> Self {
+        Self([
+            info.flags.as_raw(),
+            info.mag_filter.as_raw() as u32,
+            info.min_filter.as_raw() as u32,
+            info.mipmap_mode.as_raw() as u32,
+            info.address_mode_u.as_raw() as u32,
+            info.address_mode_v.as_raw() as u32,
+            info.address_mode_w.as_raw() as u32,
+            info.mip_lod_bias.to_bits(),
+            info.anisotropy_enable,
+            info.max_anisotropy.to_bits(),
+            info.compare_enable,
+            info.compare_op.as_raw() as u32,
+            info.min_lod.to_bits(),
+            info.max_lod.to_bits(),
+            info.border_color.as_raw() as u32,
+            info.unnormalized_coordinates,
+        ])
+    }
+}
+
+type BindingsWithSamplers = (
+    Cow<'static, [vk::DescriptorSetLayoutBinding]>,
+    Vec<Vec<vk::Sampler>>,
+);
+
+/// Points the bindings to their immutable samplers, which are kept alive by
+/// the returned vector until the layout is created.
+pub(crate) unsafe fn with_immutable_samplers(
+    device: &ash::Device, cache: &mut SamplerCache,
+    bindings: &'static [vk::DescriptorSetLayoutBinding], immutable_samplers: &[ImmutableSamplers],
+) -> VkResult<BindingsWithSamplers> {
+    if immutable_samplers.is_empty() {
+        return Ok((Cow::Borrowed(bindings), Vec::new()));
+    }
+
+    let mut bindings = bindings.to_vec();
+    let mut samplers = Vec::with_capacity(immutable_samplers.len());
+    for immutable in immutable_samplers {
+        let binding_samplers = immutable
+            .create_infos
+            .iter()
+            .map(|create_info| cache.get_or_create(device, create_info))
+            .collect::<VkResult<Vec<_>>>()?;
+
+        if let Some(binding) = bindings
+            .iter_mut()
+            .find(|binding| binding.binding == immutable.binding)
+        {
+            binding.p_immutable_samplers = binding_samplers.as_ptr();
+        }
+        samplers.push(binding_samplers);
+    }
+
+    Ok((Cow::Owned(bindings), samplers))
+}
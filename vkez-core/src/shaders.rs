@@ -1,13 +1,46 @@
-use std::ffi::CStr;
+use std::{ffi::CStr, mem, slice};
 
 use ash::{prelude::VkResult, vk};
 
+/// Embeds a SPIR-V binary as a `&'static [u32]`, the file must be in native
+/// endianness.
+#[macro_export]
+macro_rules! include_spirv {
+    ($path:expr) => {{
+        #[repr(C)]
+        struct Aligned<Bytes: ?Sized> {
+            _align: [u32; 0],
+            bytes: Bytes,
+        }
+
+        const ALIGNED: &Aligned<[u8]> = &Aligned {
+            _align: [],
+            bytes: *include_bytes!($path),
+        };
+
+        unsafe {
+            ::std::slice::from_raw_parts(
+                ALIGNED.bytes.as_ptr().cast::<u32>(),
+                ALIGNED.bytes.len() / 4,
+            )
+        }
+    }};
+}
+
 pub unsafe trait RawShaderInfo {
+    /// Hex SHA-256 of `CODE` and of the options it was compiled with, stable
+    /// across builds. Suitable as a key for pipeline caches, or to tell
+    /// whether a shader loaded at runtime is the one compiled in.
     const VIBE_CHECK: &'static str;
     const CODE: &'static [u32];
+    /// Stage of the entry point.
     const STAGE: vk::ShaderStageFlags;
+    const ENTRY_POINT: &'static CStr;
 
-    fn entry_point() -> &'static CStr;
+    #[inline]
+    fn entry_point() -> &'static CStr {
+        Self::ENTRY_POINT
+    }
 
     #[inline]
     unsafe fn create_shader_module(device: &ash::Device) -> VkResult<vk::ShaderModule> {
@@ -28,3 +61,90 @@ pub unsafe trait RawShaderInfo {
             .build()
     }
 }
+
+/// Shader with a push constant block, `PushConstants` has the same layout as
+/// the block.
+pub unsafe trait ShaderPushConstants: RawShaderInfo {
+    type PushConstants: Copy;
+
+    const RANGE: vk::PushConstantRange;
+
+    #[inline]
+    unsafe fn cmd_push_constants(
+        device: &ash::Device, command_buffer: vk::CommandBuffer, layout: vk::PipelineLayout,
+        constants: &Self::PushConstants,
+    ) {
+        debug_assert!(
+            (Self::RANGE.offset + Self::RANGE.size) as usize
+                <= mem::size_of::<Self::PushConstants>()
+        );
+
+        let bytes = slice::from_raw_parts(
+            (constants as *const Self::PushConstants as *const u8).add(Self::RANGE.offset as usize),
+            Self::RANGE.size as usize,
+        );
+        device.cmd_push_constants(
+            command_buffer,
+            layout,
+            Self::RANGE.stage_flags,
+            Self::RANGE.offset,
+            bytes,
+        );
+    }
+}
+
+/// Shader with specialization constants, `Specialization` holds their values
+/// and defaults to the values of the shader.
+pub unsafe trait ShaderSpecialization: RawShaderInfo {
+    type Specialization: Copy + Default;
+
+    const MAP_ENTRIES: &'static [vk::SpecializationMapEntry];
+
+    /// Points to `constants`, which must outlive the returned struct.
+    #[inline]
+    fn specialization_info(constants: &Self::Specialization) -> vk::SpecializationInfo {
+        vk::SpecializationInfo {
+            map_entry_count: Self::MAP_ENTRIES.len() as u32,
+            p_map_entries: Self::MAP_ENTRIES.as_ptr(),
+            data_size: mem::size_of::<Self::Specialization>(),
+            p_data: constants as *const Self::Specialization as *const _,
+        }
+    }
+
+    /// The stage info points to `specialization_info`, which is filled from
+    /// `constants`. Both are owned by the caller and must outlive the returned
+    /// struct.
+    #[inline]
+    unsafe fn pipeline_shader_stage_info_specialized(
+        module: vk::ShaderModule, constants: &Self::Specialization,
+        specialization_info: &mut vk::SpecializationInfo,
+    ) -> vk::PipelineShaderStageCreateInfo {
+        *specialization_info = Self::specialization_info(constants);
+        vk::PipelineShaderStageCreateInfo {
+            p_specialization_info: specialization_info,
+            ..Self::pipeline_shader_stage_info(module)
+        }
+    }
+}
+
+/// Shader dispatched in workgroups, like compute, task and mesh shaders.
+pub unsafe trait ComputeShader: RawShaderInfo {
+    /// Workgroup size with the default specialization.
+    const LOCAL_SIZE: [u32; 3];
+
+    /// Number of workgroups covering `invocations`.
+    #[inline]
+    fn group_count(invocations: [u32; 3]) -> [u32; 3] {
+        group_count(invocations, Self::LOCAL_SIZE)
+    }
+}
+
+/// Number of workgroups of `local_size` covering `invocations`, rounded up.
+#[inline]
+pub const fn group_count(invocations: [u32; 3], local_size: [u32; 3]) -> [u32; 3] {
+    [
+        invocations[0].div_ceil(local_size[0]),
+        invocations[1].div_ceil(local_size[1]),
+        invocations[2].div_ceil(local_size[2]),
+    ]
+}
@@ -0,0 +1,456 @@
+use ash::vk;
+
+use crate::shaders::RawShaderInfo;
+
+/// Input of a vertex shader, or attribute of a vertex struct.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub format: vk::Format,
+}
+
+/// Vertex shader with its reflected inputs.
+pub unsafe trait VertexShader: RawShaderInfo {
+    const INPUTS: &'static [VertexAttribute];
+}
+
+/// Rust type of a vertex attribute.
+///
+/// Only integer and float formats are inferred from the type, normalized,
+/// scaled and packed formats are given with `#[vertex(format = ...)]`.
+pub unsafe trait VertexFormat {
+    /// Format of each attribute.
+    const FORMAT: vk::Format;
+    /// Attributes taken by the type, one per column of a matrix or element of
+    /// an array of vectors, laid out one after the other.
+    const ATTRIBUTES: u32 = 1;
+}
+
+macro_rules! vertex_formats {
+    ($($scalar:ty => [$r:ident, $rg:ident, $rgb:ident, $rgba:ident]),* $(,)?) => {
+        $(
+            vertex_formats!(@format $scalar, $r);
+            vertex_formats!(@format [$scalar; 1], $r);
+            vertex_formats!(@format [$scalar; 2], $rg);
+            vertex_formats!(@format [$scalar; 3], $rgb);
+            vertex_formats!(@format [$scalar; 4], $rgba);
+            vertex_formats!(@columns [$scalar; 2], $rg);
+            vertex_formats!(@columns [$scalar; 3], $rgb);
+            vertex_formats!(@columns [$scalar; 4], $rgba);
+        )*
+    };
+    (@format $ty:ty, $format:ident) => {
+        unsafe impl VertexFormat for $ty {
+            const FORMAT: vk::Format = vk::Format::$format;
+        }
+    };
+    // Matrices and arrays of vectors
+    (@columns $column:ty, $format:ident) => {
+        unsafe impl<const N: usize> VertexFormat for [$column; N] {
+            const FORMAT: vk::Format = vk::Format::$format;
+            const ATTRIBUTES: u32 = N as u32;
+        }
+    };
+}
+
+vertex_formats! {
+    f32 => [R32_SFLOAT, R32G32_SFLOAT, R32G32B32_SFLOAT, R32G32B32A32_SFLOAT],
+    f64 => [R64_SFLOAT, R64G64_SFLOAT, R64G64B64_SFLOAT, R64G64B64A64_SFLOAT],
+    i8 => [R8_SINT, R8G8_SINT, R8G8B8_SINT, R8G8B8A8_SINT],
+    u8 => [R8_UINT, R8G8_UINT, R8G8B8_UINT, R8G8B8A8_UINT],
+    i16 => [R16_SINT, R16G16_SINT, R16G16B16_SINT, R16G16B16A16_SINT],
+    u16 => [R16_UINT, R16G16_UINT, R16G16B16_UINT, R16G16B16A16_UINT],
+    i32 => [R32_SINT, R32G32_SINT, R32G32B32_SINT, R32G32B32A32_SINT],
+    u32 => [R32_UINT, R32G32_UINT, R32G32B32_UINT, R32G32B32A32_UINT],
+    i64 => [R64_SINT, R64G64_SINT, R64G64B64_SINT, R64G64B64A64_SINT],
+    u64 => [R64_UINT, R64G64_UINT, R64G64B64_UINT, R64G64B64A64_UINT],
+}
+
+/// Vertex buffer element, usually implemented with `#[derive(Vertex)]`.
+pub unsafe trait Vertex: Copy {
+    /// Attributes of the vertex, in binding `0`.
+    const ATTRIBUTES: &'static [vk::VertexInputAttributeDescription];
+    const INPUT_RATE: vk::VertexInputRate;
+
+    #[inline]
+    fn binding_description(binding: u32) -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription {
+            binding,
+            stride: std::mem::size_of::<Self>() as u32,
+            input_rate: Self::INPUT_RATE,
+        }
+    }
+
+    fn attribute_descriptions(binding: u32) -> Vec<vk::VertexInputAttributeDescription> {
+        Self::ATTRIBUTES
+            .iter()
+            .map(|attribute| vk::VertexInputAttributeDescription {
+                binding,
+                ..*attribute
+            })
+            .collect()
+    }
+}
+
+/// Field of a `#[derive(Vertex)]` struct.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug)]
+pub struct VertexField {
+    /// Defaults to the location after the previous field.
+    pub location: Option<u32>,
+    pub format: vk::Format,
+    pub offset: u32,
+    /// Attributes of the field, `stride` bytes apart.
+    pub attributes: u32,
+    pub stride: u32,
+}
+
+#[doc(hidden)]
+pub const fn vertex_attribute_count(fields: &[VertexField]) -> usize {
+    let mut count = 0;
+    let mut i = 0;
+    while i < fields.len() {
+        count += fields[i].attributes as usize;
+        i += 1;
+    }
+
+    count
+}
+
+/// Attributes of the fields, each at the location after the previous one
+/// unless the field has an explicit location.
+#[doc(hidden)]
+pub const fn vertex_attributes<const N: usize>(
+    fields: &[VertexField],
+) -> [vk::VertexInputAttributeDescription; N] {
+    let mut attributes = [vk::VertexInputAttributeDescription {
+        location: 0,
+        binding: 0,
+        format: vk::Format::UNDEFINED,
+        offset: 0,
+    }; N];
+
+    let mut location = 0;
+    let mut n = 0;
+    let mut i = 0;
+    while i < fields.len() {
+        let field = &fields[i];
+        if let Some(explicit) = field.location {
+            location = explicit;
+        }
+
+        let mut j = 0;
+        while j < field.attributes {
+            attributes[n] = vk::VertexInputAttributeDescription {
+                location,
+                binding: 0,
+                format: field.format,
+                offset: field.offset + j * field.stride,
+            };
+            location += format_locations(field.format);
+            n += 1;
+            j += 1;
+        }
+        i += 1;
+    }
+
+    attributes
+}
+
+/// Locations taken by an attribute, 64-bit vectors of 3 and 4 components
+/// take two.
+const fn format_locations(format: vk::Format) -> u32 {
+    match format_numeric_type(format) {
+        Some((NumericType::Float64 | NumericType::SInt64 | NumericType::UInt64, 3 | 4)) => 2,
+        _ => 1,
+    }
+}
+
+/// `true` when `attributes` provide every input of the shader, and nothing
+/// else, with compatible formats.
+pub const fn attributes_match_inputs(
+    inputs: &[VertexAttribute], attributes: &[vk::VertexInputAttributeDescription],
+) -> bool {
+    if inputs.len() != attributes.len() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < inputs.len() {
+        let mut found = false;
+        let mut j = 0;
+        while j < attributes.len() {
+            if attributes[j].location == inputs[i].location {
+                if !format_feeds_input(attributes[j].format, inputs[i].format) {
+                    return false;
+                }
+                found = true;
+            }
+            j += 1;
+        }
+
+        if !found {
+            return false;
+        }
+        i += 1;
+    }
+
+    true
+}
+
+/// `true` when an attribute of the format can feed an input of the shader,
+/// they must have the same numeric type and the attribute can't have more
+/// components than the input, the missing ones are filled in.
+pub const fn format_feeds_input(attribute: vk::Format, input: vk::Format) -> bool {
+    match (format_numeric_type(attribute), format_numeric_type(input)) {
+        (Some((attribute_type, attribute_count)), Some((input_type, input_count))) => {
+            attribute_type as u32 == input_type as u32 && attribute_count <= input_count
+        }
+        _ => attribute.as_raw() == input.as_raw(),
+    }
+}
+
+/// Type of the components of a format as seen by the shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NumericType {
+    /// Float, normalized and scaled formats.
+    Float,
+    SInt,
+    UInt,
+    Float64,
+    SInt64,
+    UInt64,
+}
+
+/// Numeric type and component count of the vertex buffer formats.
+const fn format_numeric_type(format: vk::Format) -> Option<(NumericType, u32)> {
+    use vk::Format as F;
+    use NumericType::*;
+
+    Some(match format {
+        F::R8_UNORM
+        | F::R8_SNORM
+        | F::R8_USCALED
+        | F::R8_SSCALED
+        | F::R8_SRGB
+        | F::R16_UNORM
+        | F::R16_SNORM
+        | F::R16_USCALED
+        | F::R16_SSCALED
+        | F::R16_SFLOAT
+        | F::R32_SFLOAT => (Float, 1),
+        F::R8G8_UNORM
+        | F::R8G8_SNORM
+        | F::R8G8_USCALED
+        | F::R8G8_SSCALED
+        | F::R8G8_SRGB
+        | F::R16G16_UNORM
+        | F::R16G16_SNORM
+        | F::R16G16_USCALED
+        | F::R16G16_SSCALED
+        | F::R16G16_SFLOAT
+        | F::R32G32_SFLOAT => (Float, 2),
+        F::R8G8B8_UNORM
+        | F::R8G8B8_SNORM
+        | F::R8G8B8_USCALED
+        | F::R8G8B8_SSCALED
+        | F::R8G8B8_SRGB
+        | F::B8G8R8_UNORM
+        | F::B8G8R8_SNORM
+        | F::B8G8R8_USCALED
+        | F::B8G8R8_SSCALED
+        | F::B8G8R8_SRGB
+        | F::R16G16B16_UNORM
+        | F::R16G16B16_SNORM
+        | F::R16G16B16_USCALED
+        | F::R16G16B16_SSCALED
+        | F::R16G16B16_SFLOAT
+        | F::R32G32B32_SFLOAT
+        | F::B10G11R11_UFLOAT_PACK32
+        | F::E5B9G9R9_UFLOAT_PACK32 => (Float, 3),
+        F::R8G8B8A8_UNORM
+        | F::R8G8B8A8_SNORM
+        | F::R8G8B8A8_USCALED
+        | F::R8G8B8A8_SSCALED
+        | F::R8G8B8A8_SRGB
+        | F::B8G8R8A8_UNORM
+        | F::B8G8R8A8_SNORM
+        | F::B8G8R8A8_USCALED
+        | F::B8G8R8A8_SSCALED
+        | F::B8G8R8A8_SRGB
+        | F::A8B8G8R8_UNORM_PACK32
+        | F::A8B8G8R8_SNORM_PACK32
+        | F::A8B8G8R8_USCALED_PACK32
+        | F::A8B8G8R8_SSCALED_PACK32
+        | F::A8B8G8R8_SRGB_PACK32
+        | F::A2R10G10B10_UNORM_PACK32
+        | F::A2R10G10B10_SNORM_PACK32
+        | F::A2R10G10B10_USCALED_PACK32
+        | F::A2R10G10B10_SSCALED_PACK32
+        | F::A2B10G10R10_UNORM_PACK32
+        | F::A2B10G10R10_SNORM_PACK32
+        | F::A2B10G10R10_USCALED_PACK32
+        | F::A2B10G10R10_SSCALED_PACK32
+        | F::R16G16B16A16_UNORM
+        | F::R16G16B16A16_SNORM
+        | F::R16G16B16A16_USCALED
+        | F::R16G16B16A16_SSCALED
+        | F::R16G16B16A16_SFLOAT
+        | F::R32G32B32A32_SFLOAT => (Float, 4),
+        F::R8_SINT | F::R16_SINT | F::R32_SINT => (SInt, 1),
+        F::R8G8_SINT | F::R16G16_SINT | F::R32G32_SINT => (SInt, 2),
+        F::R8G8B8_SINT | F::B8G8R8_SINT | F::R16G16B16_SINT | F::R32G32B32_SINT => (SInt, 3),
+        F::R8G8B8A8_SINT
+        | F::B8G8R8A8_SINT
+        | F::A8B8G8R8_SINT_PACK32
+        | F::A2R10G10B10_SINT_PACK32
+        | F::A2B10G10R10_SINT_PACK32
+        | F::R16G16B16A16_SINT
+        | F::R32G32B32A32_SINT => (SInt, 4),
+        F::R8_UINT | F::R16_UINT | F::R32_UINT => (UInt, 1),
+        F::R8G8_UINT | F::R16G16_UINT | F::R32G32_UINT => (UInt, 2),
+        F::R8G8B8_UINT | F::B8G8R8_UINT | F::R16G16B16_UINT | F::R32G32B32_UINT => (UInt, 3),
+        F::R8G8B8A8_UINT
+        | F::B8G8R8A8_UINT
+        | F::A8B8G8R8_UINT_PACK32
+        | F::A2R10G10B10_UINT_PACK32
+        | F::A2B10G10R10_UINT_PACK32
+        | F::R16G16B16A16_UINT
+        | F::R32G32B32A32_UINT => (UInt, 4),
+        F::R64_SFLOAT => (Float64, 1),
+        F::R64G64_SFLOAT => (Float64, 2),
+        F::R64G64B64_SFLOAT => (Float64, 3),
+        F::R64G64B64A64_SFLOAT => (Float64, 4),
+        F::R64_SINT => (SInt64, 1),
+        F::R64G64_SINT => (SInt64, 2),
+        F::R64G64B64_SINT => (SInt64, 3),
+        F::R64G64B64A64_SINT => (SInt64, 4),
+        F::R64_UINT => (UInt64, 1),
+        F::R64G64_UINT => (UInt64, 2),
+        F::R64G64B64_UINT => (UInt64, 3),
+        F::R64G64B64A64_UINT => (UInt64, 4),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(location: Option<u32>, format: vk::Format, offset: u32) -> VertexField {
+        VertexField {
+            location,
+            format,
+            offset,
+            attributes: 1,
+            stride: 0,
+        }
+    }
+
+    fn locations<const N: usize>(fields: &[VertexField]) -> [(u32, u32); N] {
+        assert_eq!(vertex_attribute_count(fields), N);
+        vertex_attributes::<N>(fields).map(|attribute| (attribute.location, attribute.offset))
+    }
+
+    #[test]
+    fn matrix_takes_a_location_per_column() {
+        let fields = [
+            VertexField {
+                attributes: <[[f32; 4]; 4] as VertexFormat>::ATTRIBUTES,
+                stride: 16,
+                ..field(None, <[[f32; 4]; 4]>::FORMAT, 0)
+            },
+            field(None, <[f32; 3]>::FORMAT, 64),
+        ];
+
+        assert_eq!(locations(&fields), [
+            (0, 0),
+            (1, 16),
+            (2, 32),
+            (3, 48),
+            (4, 64)
+        ]);
+    }
+
+    #[test]
+    fn double_vectors_take_two_locations() {
+        let fields = [
+            field(None, <[f64; 4]>::FORMAT, 0),
+            field(None, <[f64; 2]>::FORMAT, 32),
+            field(None, <[f64; 3]>::FORMAT, 48),
+            field(None, f32::FORMAT, 72),
+        ];
+
+        assert_eq!(locations(&fields), [(0, 0), (2, 32), (3, 48), (5, 72)]);
+    }
+
+    #[test]
+    fn explicit_locations() {
+        let fields = [
+            field(Some(2), vk::Format::R32G32_SFLOAT, 0),
+            field(None, vk::Format::R32G32_SFLOAT, 8),
+            field(Some(0), vk::Format::R32_UINT, 16),
+        ];
+
+        assert_eq!(locations(&fields), [(2, 0), (3, 8), (0, 16)]);
+    }
+
+    #[test]
+    fn compatible_formats() {
+        let vec4 = vk::Format::R32G32B32A32_SFLOAT;
+        assert!(format_feeds_input(vk::Format::R8G8B8A8_UNORM, vec4));
+        assert!(format_feeds_input(
+            vk::Format::A2B10G10R10_SNORM_PACK32,
+            vec4
+        ));
+        assert!(format_feeds_input(vk::Format::R16G16_SFLOAT, vec4));
+        assert!(format_feeds_input(
+            vk::Format::R8G8B8A8_UINT,
+            vk::Format::R32G32B32A32_UINT
+        ));
+
+        // Different numeric type
+        assert!(!format_feeds_input(vk::Format::R8G8B8A8_UINT, vec4));
+        assert!(!format_feeds_input(
+            vk::Format::R32_SINT,
+            vk::Format::R32_UINT
+        ));
+        assert!(!format_feeds_input(
+            vk::Format::R64_SFLOAT,
+            vk::Format::R32_SFLOAT
+        ));
+        // More components than the input
+        assert!(!format_feeds_input(
+            vk::Format::R8G8B8A8_UNORM,
+            vk::Format::R32G32_SFLOAT
+        ));
+    }
+
+    #[test]
+    fn attributes_and_inputs() {
+        let inputs = [
+            VertexAttribute {
+                location: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+            },
+            VertexAttribute {
+                location: 1,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+            },
+        ];
+        let attributes = vertex_attributes::<2>(&[
+            field(None, vk::Format::R32G32B32_SFLOAT, 0),
+            field(None, vk::Format::R8G8B8A8_UNORM, 12),
+        ]);
+        assert!(attributes_match_inputs(&inputs, &attributes));
+
+        // Missing input
+        assert!(!attributes_match_inputs(&inputs, &attributes[..1]));
+        // Wrong location
+        let attributes = vertex_attributes::<2>(&[
+            field(None, vk::Format::R32G32B32_SFLOAT, 0),
+            field(Some(2), vk::Format::R8G8B8A8_UNORM, 12),
+        ]);
+        assert!(!attributes_match_inputs(&inputs, &attributes));
+    }
+}
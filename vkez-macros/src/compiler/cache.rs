@@ -0,0 +1,176 @@
+//! Compiled SPIR-V is kept under the target directory so unchanged shaders are
+//! not recompiled on every build, and so the generated code can embed it with
+//! `include_bytes!` instead of a literal of every word.
+//!
+//! An entry is looked up in two steps: the key of the source and options gives
+//! the list of files it included the last time it was compiled, then the
+//! contents of these files complete the key of the SPIR-V. The warnings of the
+//! compilation are kept next to it so they are reported again on a hit, an
+//! entry without them, as written by earlier versions, is a miss and is
+//! compiled again.
+//!
+//! The cache is in `OUT_DIR` for crates with a build script. Otherwise it is in
+//! the target directory, found from the directory rustc runs in: the workspace
+//! root for workspace members, but the source directory of the package for a
+//! dependency from a registry or git. Such dependencies should add a build
+//! script, or users can set `VKEZ_SHADER_CACHE_DIR` to an absolute path.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+const CACHE_DIR_ENV_VAR: &str = "VKEZ_SHADER_CACHE_DIR";
+
+pub(crate) struct ShaderCache {
+    dir: PathBuf,
+}
+
+pub(crate) struct CachedShader {
+    pub path: PathBuf,
+    pub code: Vec<u32>,
+    pub includes: Vec<PathBuf>,
+    /// Log of the compiler, empty when it had nothing to say.
+    pub warnings: String,
+}
+
+impl ShaderCache {
+    /// `$VKEZ_SHADER_CACHE_DIR`, or `vkez-shaders` in `OUT_DIR` or in the
+    /// target directory. `None` when the directory cannot be created.
+    pub fn open() -> Option<Self> {
+        let dir = match std::env::var_os(CACHE_DIR_ENV_VAR) {
+            // Relative to the directory of rustc like the target directory,
+            // the path ends up in `include_bytes!` which resolves relative
+            // paths next to the invoking file
+            Some(dir) => std::env::current_dir().ok()?.join(dir),
+            None => match std::env::var_os("OUT_DIR") {
+                Some(out_dir) => PathBuf::from(out_dir).join("vkez-shaders"),
+                None => target_dir()?.join("vkez-shaders"),
+            },
+        };
+
+        fs::create_dir_all(&dir).ok()?;
+        Some(Self { dir })
+    }
+
+    pub fn get(&self, key: &str) -> Option<CachedShader> {
+        let deps = fs::read_to_string(self.dir.join(format!("{key}.deps"))).ok()?;
+        let includes = deps.lines().map(PathBuf::from).collect::<Vec<_>>();
+
+        let path = self.spirv_path(key, &includes)?;
+        let bytes = fs::read(&path).ok()?;
+        if !bytes.len().is_multiple_of(4) {
+            return None;
+        }
+
+        let code = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+            .collect();
+        let warnings = fs::read_to_string(path.with_extension("log")).ok()?;
+
+        Some(CachedShader {
+            path,
+            code,
+            includes,
+            warnings,
+        })
+    }
+
+    /// Path of the written SPIR-V, `None` if it could not be written.
+    pub fn insert(
+        &self, key: &str, code: &[u32], includes: &[PathBuf], warnings: &str,
+    ) -> Option<PathBuf> {
+        let path = self.spirv_path(key, includes)?;
+        let bytes = code
+            .iter()
+            .flat_map(|word| word.to_ne_bytes())
+            .collect::<Vec<_>>();
+        // Before the SPIR-V so that a hit always has both
+        write_atomic(&path.with_extension("log"), warnings.as_bytes())?;
+        write_atomic(&path, &bytes)?;
+
+        let deps = includes
+            .iter()
+            .map(|include| format!("{}\n", include.display()))
+            .collect::<String>();
+        write_atomic(&self.dir.join(format!("{key}.deps")), deps.as_bytes())?;
+
+        Some(path)
+    }
+
+    /// `None` if an include cannot be read anymore.
+    fn spirv_path(&self, key: &str, includes: &[PathBuf]) -> Option<PathBuf> {
+        let mut hasher = Sha256::new();
+        hasher.update(key);
+        for include in includes {
+            hasher.update(include.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            hasher.update(fs::read(include).ok()?);
+            hasher.update([0]);
+        }
+
+        Some(self.dir.join(format!("{}.spv", hex(&hasher.finalize()))))
+    }
+}
+
+/// Hash of every input of a compilation, `parts` are separated so that moving
+/// bytes from one to the next changes the key.
+pub(crate) fn cache_key<I>(parts: I) -> String
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    for part in parts {
+        hasher.update([0]);
+        hasher.update(part);
+    }
+
+    hex(&hasher.finalize())
+}
+
+/// Unlike the cache keys, only depends on the output and options of the
+/// compiler, so it is the same across machines, paths and vkez versions.
+pub(crate) fn content_hash(code: &[u32], compile_inputs: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    for word in code {
+        hasher.update(word.to_le_bytes());
+    }
+    for input in compile_inputs {
+        hasher.update([0]);
+        hasher.update(input);
+    }
+
+    hex(&hasher.finalize())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Several crates may compile the same shader at once, readers must never see
+/// a partially written file.
+fn write_atomic(path: &Path, contents: &[u8]) -> Option<()> {
+    let temp = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&temp, contents).ok()?;
+    fs::rename(&temp, path).ok().or_else(|| {
+        let _ = fs::remove_file(&temp);
+        None
+    })
+}
+
+/// Proc macros are not told where the target directory is, it is
+/// `$CARGO_TARGET_DIR` or `target` in the workspace root, which is where rustc
+/// runs from for workspace members only.
+fn target_dir() -> Option<PathBuf> {
+    let current_dir = std::env::current_dir().ok()?;
+    let target_dir = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target"));
+
+    Some(current_dir.join(target_dir))
+}
@@ -0,0 +1,222 @@
+use std::collections::HashSet;
+
+use proc_macro_error::{emit_error, emit_warning};
+use syn::LitStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+/// One message of shaderc, like `file.glsl:12: error: 'x' : undeclared
+/// identifier`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ShaderDiagnostic {
+    severity: Severity,
+    file: Option<String>,
+    line: Option<usize>,
+    message: String,
+}
+
+impl ShaderDiagnostic {
+    fn parse(line: &str) -> Option<Self> {
+        let (location, severity, message) = [
+            (": error: ", Severity::Error),
+            (": warning: ", Severity::Warning),
+        ]
+        .into_iter()
+        .find_map(|(separator, severity)| {
+            let (location, message) = line.split_once(separator)?;
+            Some((location, severity, message))
+        })?;
+
+        // The line is after the last `:` so that Windows drive letters are
+        // kept in the file name
+        let (file, line) = match location.rsplit_once(':') {
+            Some((file, line)) if line.chars().all(|c| c.is_ascii_digit()) => {
+                (file, line.parse().ok())
+            }
+            _ => (location, None),
+        };
+
+        Some(Self {
+            severity,
+            file: Some(file.to_string()).filter(|file| !file.is_empty()),
+            line,
+            message: message.trim().to_string(),
+        })
+    }
+
+    /// `file:line` followed by the offending source line, when available.
+    fn note(&self) -> Option<String> {
+        let file = self.file.as_ref()?;
+        let Some(line) = self.line else {
+            return Some(file.clone());
+        };
+
+        let snippet = std::fs::read_to_string(file).ok().and_then(|source| {
+            let source_line = source.lines().nth(line.checked_sub(1)?)?;
+            Some(format!("\n{line:>4} | {}", source_line.trim_end()))
+        });
+
+        Some(format!("{file}:{line}{}", snippet.unwrap_or_default()))
+    }
+}
+
+/// Emits every message of a shaderc log as its own diagnostic on `span`,
+/// duplicates are only reported once. Returns the number of errors.
+pub(crate) fn emit_shader_diagnostics(span: &LitStr, messages: &str) -> usize {
+    let mut diagnostics = Vec::<ShaderDiagnostic>::new();
+    for line in messages.lines() {
+        let line = line.trim_end();
+        if line.is_empty() || is_summary(line) {
+            continue;
+        }
+
+        match ShaderDiagnostic::parse(line) {
+            Some(diagnostic) => diagnostics.push(diagnostic),
+            // Continuation of the previous message
+            None => match diagnostics.last_mut() {
+                Some(last) => {
+                    last.message.push('\n');
+                    last.message.push_str(line);
+                }
+                None => diagnostics.push(ShaderDiagnostic {
+                    severity: Severity::Error,
+                    file: None,
+                    line: None,
+                    message: line.to_string(),
+                }),
+            },
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut errors = 0;
+    for diagnostic in diagnostics {
+        if !seen.insert(diagnostic.clone()) {
+            continue;
+        }
+
+        let message = &diagnostic.message;
+        match (diagnostic.severity, diagnostic.note()) {
+            (Severity::Error, Some(note)) => emit_error!(span, "{}", message; note = note),
+            (Severity::Error, None) => emit_error!(span, "{}", message),
+            (Severity::Warning, Some(note)) => emit_warning!(span, "{}", message; note = note),
+            (Severity::Warning, None) => emit_warning!(span, "{}", message),
+        }
+
+        if diagnostic.severity == Severity::Error {
+            errors += 1;
+        }
+    }
+
+    errors
+}
+
+/// `1 error generated.`, `2 warnings generated.`
+fn is_summary(line: &str) -> bool {
+    line.ends_with(" generated.")
+        && line
+            .split_whitespace()
+            .next()
+            .is_some_and(|count| count.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(
+        severity: Severity, file: Option<&str>, line: Option<usize>, message: &str,
+    ) -> ShaderDiagnostic {
+        ShaderDiagnostic {
+            severity,
+            file: file.map(str::to_string),
+            line,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_error() {
+        assert_eq!(
+            ShaderDiagnostic::parse(
+                "/work/shaders/add.comp.glsl:12: error: 'x' : undeclared identifier"
+            ),
+            Some(diagnostic(
+                Severity::Error,
+                Some("/work/shaders/add.comp.glsl"),
+                Some(12),
+                "'x' : undeclared identifier"
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_warning() {
+        assert_eq!(
+            ShaderDiagnostic::parse(
+                "shaders/blur.frag:3: warning: '#extension' : extension not supported: \
+                 GL_EXT_foo"
+            ),
+            Some(diagnostic(
+                Severity::Warning,
+                Some("shaders/blur.frag"),
+                Some(3),
+                "'#extension' : extension not supported: GL_EXT_foo"
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_path_with_colons() {
+        assert_eq!(
+            ShaderDiagnostic::parse(
+                "C:\\work\\shaders\\add.comp:7: error: '' :  syntax error, unexpected \
+                 IDENTIFIER, expecting COMMA or SEMICOLON"
+            ),
+            Some(diagnostic(
+                Severity::Error,
+                Some("C:\\work\\shaders\\add.comp"),
+                Some(7),
+                "'' :  syntax error, unexpected IDENTIFIER, expecting COMMA or SEMICOLON"
+            ))
+        );
+        assert_eq!(
+            ShaderDiagnostic::parse("/tmp/build:1/add.comp:20: error: 'main' : redefinition"),
+            Some(diagnostic(
+                Severity::Error,
+                Some("/tmp/build:1/add.comp"),
+                Some(20),
+                "'main' : redefinition"
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_without_line() {
+        assert_eq!(
+            ShaderDiagnostic::parse(
+                "add.comp: error: Linking compute stage: Missing entry point: Each stage \
+                 requires one entry point"
+            ),
+            Some(diagnostic(
+                Severity::Error,
+                Some("add.comp"),
+                None,
+                "Linking compute stage: Missing entry point: Each stage requires one entry \
+                 point"
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_other_lines() {
+        assert_eq!(ShaderDiagnostic::parse("  int x = y;"), None);
+        assert!(is_summary("1 error generated."));
+        assert!(is_summary("2 warnings generated."));
+        assert!(!is_summary("add.comp:1: error: nothing generated."));
+    }
+}
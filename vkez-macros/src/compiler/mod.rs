@@ -0,0 +1,616 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+};
+
+use proc_macro2::TokenStream as TokenStream2;
+use proc_macro_error::{abort_if_dirty, emit_error};
+use quote::quote;
+use shaderc::{CompileOptions, IncludeType, ResolvedInclude, ShaderKind};
+use syn::{
+    parse::{Parse, ParseStream},
+    Ident, LitStr, Token,
+};
+use vkez_core::ash::vk;
+
+use crate::reflect::{
+    execution_model_stage, EntryPoint, ShaderEntryPoint, ShaderReflection, SpirvModule, MAGIC,
+};
+
+mod cache;
+mod diagnostics;
+mod options;
+
+use cache::{cache_key, content_hash, ShaderCache};
+pub(crate) use diagnostics::*;
+pub(crate) use options::*;
+
+/// `NAME` or `NAME = "value"`
+#[derive(Debug, Clone)]
+pub(crate) struct ShaderDefine {
+    pub name: Ident,
+    pub value: Option<LitStr>,
+}
+
+impl Parse for ShaderDefine {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+
+        Ok(Self { name, value })
+    }
+}
+
+/// Base directory of the relative paths given to a macro.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RelativeTo {
+    /// `CARGO_MANIFEST_DIR` of the crate being built.
+    #[default]
+    Manifest,
+    /// Directory of the `.rs` file invoking the macro.
+    File,
+}
+
+impl RelativeTo {
+    /// `"manifest"` or `"file"`, defaults to `"manifest"`.
+    pub fn parse(relative_to: Option<&LitStr>) -> Self {
+        let Some(relative_to) = relative_to else {
+            return Self::Manifest;
+        };
+
+        match relative_to.value().as_str() {
+            "manifest" => Self::Manifest,
+            "file" => Self::File,
+            other => {
+                emit_error!(relative_to, "Unknown base directory `{}`", other; help = "Expected manifest or file");
+                Self::Manifest
+            }
+        }
+    }
+
+    /// `span` must come from the invoking file.
+    fn base_dir(self, span: &LitStr) -> PathBuf {
+        // rustc runs from the workspace root and reports paths relative to it
+        let current_dir = std::env::current_dir().unwrap_or_default();
+
+        let base_dir = match self {
+            Self::Manifest => std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from),
+            Self::File => span
+                .span()
+                .unwrap()
+                .local_file()
+                .and_then(|file| file.parent().map(Path::to_path_buf)),
+        };
+
+        match base_dir {
+            Some(base_dir) => current_dir.join(base_dir),
+            None => current_dir,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Manifest => "relative to the crate's root, use `relative_to = \"file\"` to resolve them next to the invoking file",
+            Self::File => "relative to the invoking file",
+        }
+    }
+}
+
+pub(crate) struct ShaderCompileArgs<'a> {
+    pub path: &'a LitStr,
+    pub relative_to: RelativeTo,
+    /// Inferred from the file extension when not specified.
+    pub kind: Option<ShaderKind>,
+    /// Every entry point of the module when not specified, shaderc compiles
+    /// `main`.
+    pub entry: Option<&'a LitStr>,
+    /// Searched by `#include <...>` and as a fallback of `#include "..."`.
+    pub include_dirs: &'a [LitStr],
+    pub defines: &'a [ShaderDefine],
+    pub options: &'a ShaderCompileOptions,
+}
+
+impl ShaderCompileArgs<'_> {
+    fn entry_point(&self) -> String {
+        self.entry.map_or_else(|| "main".to_string(), LitStr::value)
+    }
+}
+
+pub(crate) struct CompiledShader {
+    pub absolute_path: PathBuf,
+    /// Every file pulled by an `#include`.
+    pub includes: Vec<PathBuf>,
+    pub code: Vec<u32>,
+    /// `code` in the shader cache, `None` when the cache is not writable.
+    pub spirv_path: Option<PathBuf>,
+    /// Hex SHA-256 of `code` and of the options it was compiled with.
+    pub content_hash: String,
+    /// Entry point selected by the arguments, or one of the entry points of
+    /// the module.
+    pub entry_point: ShaderEntryPoint,
+    pub reflection: ShaderReflection,
+}
+
+impl CompiledShader {
+    /// `&'static [u32]` expression of the SPIR-V.
+    pub fn code_tokens(&self) -> TokenStream2 {
+        match self.spirv_path.as_ref() {
+            Some(spirv_path) => {
+                let spirv_path = spirv_path.to_string_lossy();
+                quote!(::vkez_core::include_spirv!(#spirv_path))
+            }
+            None => {
+                let code = &self.code;
+                quote!(&[#(#code),*])
+            }
+        }
+    }
+}
+
+/// One shader per entry point of the module when `entry` is not given, usually
+/// a single one.
+pub(crate) fn compile_shader(args: ShaderCompileArgs) -> Result<Vec<CompiledShader>, TokenStream2> {
+    let path = PathBuf::from(args.path.value());
+    let base_dir = args.relative_to.base_dir(args.path);
+    let absolute_path = base_dir.join(&path);
+
+    let shader_bytes = match std::fs::read(&absolute_path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            emit_error!(
+                args.path, "Cannot read shader: {}", e;
+                help = "Searched `{}`", absolute_path.display();
+                help =? path
+                    .is_relative()
+                    .then(|| format!("Paths are {}", args.relative_to.describe()))
+            );
+            return Err(TokenStream2::new());
+        }
+    };
+
+    if args.options.is_precompiled(&path) {
+        return load_spirv(&args, absolute_path, &shader_bytes);
+    }
+
+    let shader_source = String::from_utf8(shader_bytes).map_err(|_| {
+        syn::Error::new(
+            args.path.span(),
+            "Shader source is not valid UTF-8, use `lang = \"spirv\"` for SPIR-V binaries",
+        )
+        .to_compile_error()
+    })?;
+
+    let include_dirs = args
+        .include_dirs
+        .iter()
+        .map(|dir| {
+            let resolved = base_dir.join(dir.value());
+            if !resolved.is_dir() {
+                emit_error!(
+                    dir, "Include directory not found";
+                    help = "Searched `{}`", resolved.display();
+                    help = "Paths are {}", args.relative_to.describe()
+                );
+            }
+            resolved
+        })
+        .collect::<Vec<_>>();
+
+    abort_if_dirty();
+
+    let shader_kind = args
+        .kind
+        .or_else(|| shader_kind_from_path(&path))
+        .unwrap_or(ShaderKind::InferFromSource);
+
+    // Everything that affects the SPIR-V besides the source files
+    let compile_inputs = [
+        format!("{shader_kind:?}"),
+        args.entry_point(),
+        args.options.cache_key(),
+    ]
+    .into_iter()
+    .chain(args.defines.iter().map(|define| {
+        format!(
+            "{}={:?}",
+            define.name,
+            define.value.as_ref().map(LitStr::value)
+        )
+    }))
+    .collect::<Vec<_>>();
+
+    let key = cache_key(
+        [
+            absolute_path.to_string_lossy().into_owned(),
+            shader_source.clone(),
+        ]
+        .into_iter()
+        .chain(
+            include_dirs
+                .iter()
+                .map(|dir| dir.to_string_lossy().into_owned()),
+        )
+        .chain(compile_inputs.iter().cloned()),
+    );
+
+    let cache = ShaderCache::open();
+    let (code, includes, spirv_path) = match cache.as_ref().and_then(|cache| cache.get(&key)) {
+        Some(cached) => {
+            // Replayed so that a warning doesn't disappear on the next build
+            emit_shader_diagnostics(args.path, &cached.warnings);
+            (cached.code, cached.includes, Some(cached.path))
+        }
+        None => {
+            let (code, includes, warnings) = run_compiler(
+                &args,
+                &path,
+                &absolute_path,
+                &shader_source,
+                shader_kind,
+                &include_dirs,
+            )?;
+            let spirv_path = cache
+                .as_ref()
+                .and_then(|cache| cache.insert(&key, &code, &includes, &warnings));
+            (code, includes, spirv_path)
+        }
+    };
+
+    let entry_points = reflect_shader(&args, &code)?;
+    let several = entry_points.len() > 1;
+
+    Ok(entry_points
+        .into_iter()
+        .map(|(entry_point, reflection)| {
+            // Entry points of the same module must not share a hash
+            let mut compile_inputs = compile_inputs.clone();
+            if several {
+                compile_inputs.push(entry_point.name.clone());
+            }
+
+            CompiledShader {
+                absolute_path: absolute_path.clone(),
+                includes: includes.clone(),
+                code: code.clone(),
+                spirv_path: spirv_path.clone(),
+                content_hash: content_hash(&code, &compile_inputs),
+                entry_point,
+                reflection,
+            }
+        })
+        .collect())
+}
+
+/// Precompiled SPIR-V is embedded as is.
+fn load_spirv(
+    args: &ShaderCompileArgs, absolute_path: PathBuf, bytes: &[u8],
+) -> Result<Vec<CompiledShader>, TokenStream2> {
+    let error = |message: String| syn::Error::new(args.path.span(), message).to_compile_error();
+
+    if !bytes.len().is_multiple_of(4) || bytes.len() < 20 {
+        return Err(error(format!(
+            "Invalid SPIR-V binary, expected a multiple of 4 bytes and at least a header, \
+             got {} bytes",
+            bytes.len()
+        )));
+    }
+
+    let mut code = bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+        .collect::<Vec<_>>();
+
+    // Modules written on a host of the other endianness are swapped, and then
+    // can't be included as is
+    let native_endian = code[0] == MAGIC;
+    if !native_endian {
+        if code[0].swap_bytes() != MAGIC {
+            return Err(error(format!(
+                "Invalid SPIR-V magic number {:#010x}, expected {MAGIC:#010x}",
+                code[0]
+            )));
+        }
+        for word in &mut code {
+            *word = word.swap_bytes();
+        }
+    }
+
+    let (major, minor) = (code[1] >> 16 & 0xff, code[1] >> 8 & 0xff);
+    if major != 1 || minor > 6 {
+        return Err(error(format!(
+            "Unsupported SPIR-V version {major}.{minor}, expected 1.0 to 1.6"
+        )));
+    }
+
+    for define in args.defines {
+        emit_error!(define.name, "Defines have no effect on precompiled SPIR-V");
+    }
+    abort_if_dirty();
+
+    let entry_points = reflect_shader(args, &code)?;
+
+    Ok(entry_points
+        .into_iter()
+        .map(|(entry_point, reflection)| CompiledShader {
+            spirv_path: native_endian.then(|| absolute_path.clone()),
+            absolute_path: absolute_path.clone(),
+            includes: Vec::new(),
+            code: code.clone(),
+            content_hash: content_hash(&code, &["spirv".to_string(), entry_point.name.clone()]),
+            entry_point,
+            reflection,
+        })
+        .collect())
+}
+
+/// The entry point named in the arguments, or every entry point of the module,
+/// checked against the kind.
+fn select_entry_points<'m>(
+    args: &ShaderCompileArgs, module: &'m SpirvModule,
+) -> Result<Vec<&'m EntryPoint>, TokenStream2> {
+    let entry_points = module
+        .entry_points
+        .iter()
+        .filter(|entry| !execution_model_stage(entry.execution_model).is_empty())
+        .collect::<Vec<_>>();
+
+    if entry_points.is_empty() {
+        return Err(syn::Error::new(
+            args.path.span(),
+            "Shader has no entry point with a known stage",
+        )
+        .to_compile_error());
+    }
+
+    let selected = match args.entry {
+        Some(entry) => {
+            let name = entry.value();
+            let Some(entry_point) = entry_points.iter().find(|entry| entry.name == name) else {
+                let available = entry_points
+                    .iter()
+                    .map(|entry| {
+                        format!(
+                            "`{}` ({:?})",
+                            entry.name,
+                            execution_model_stage(entry.execution_model)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                emit_error!(
+                    entry, "No entry point named `{}` in the shader", name;
+                    help = "Available entry points: {}", available;
+                    help =? (entry_points.len() > 1).then_some(
+                        "Leave out `entry` to get a shader per entry point"
+                    )
+                );
+                return Err(TokenStream2::new());
+            };
+            vec![*entry_point]
+        }
+        None => entry_points,
+    };
+
+    if let Some(stage) = args.kind.and_then(shader_kind_stage) {
+        for entry_point in &selected {
+            let entry_stage = execution_model_stage(entry_point.execution_model);
+            if stage != entry_stage {
+                return Err(syn::Error::new(
+                    args.path.span(),
+                    format!(
+                        "Entry point `{}` is a {:?} shader, not {:?}",
+                        entry_point.name, entry_stage, stage
+                    ),
+                )
+                .to_compile_error());
+            }
+        }
+    }
+
+    Ok(selected)
+}
+
+/// Selects the entry points and reflects what each of them uses.
+fn reflect_shader(
+    args: &ShaderCompileArgs, code: &[u32],
+) -> Result<Vec<(ShaderEntryPoint, ShaderReflection)>, TokenStream2> {
+    let error = |e: String| {
+        syn::Error::new(args.path.span(), format!("Failed to reflect shader: {e}"))
+            .to_compile_error()
+    };
+
+    let module = SpirvModule::parse(code).map_err(error)?;
+    select_entry_points(args, &module)?
+        .into_iter()
+        .map(|entry_point| {
+            Ok((
+                ShaderEntryPoint::reflect(&module, entry_point).map_err(error)?,
+                ShaderReflection::reflect(&module, entry_point).map_err(error)?,
+            ))
+        })
+        .collect()
+}
+
+/// SPIR-V, the sorted list of included files and the warnings, which are
+/// already emitted.
+fn run_compiler(
+    args: &ShaderCompileArgs, path: &Path, absolute_path: &Path, shader_source: &str,
+    shader_kind: ShaderKind, include_dirs: &[PathBuf],
+) -> Result<(Vec<u32>, Vec<PathBuf>, String), TokenStream2> {
+    let compiler = shaderc::Compiler::new().unwrap();
+
+    let includes = RefCell::new(Vec::new());
+
+    let mut compile_options = CompileOptions::new().unwrap();
+    let language = args.options.source_language(path);
+    args.options
+        .apply(&mut compile_options, language, args.path);
+    for define in args.defines {
+        compile_options.add_macro_definition(
+            &define.name.to_string(),
+            define.value.as_ref().map(|v| v.value()).as_deref(),
+        );
+    }
+    compile_options.set_include_callback(|requested, include_type, requesting, _depth| {
+        // `#include "..."` is searched next to the including file first
+        let relative = match include_type {
+            IncludeType::Relative => Path::new(requesting)
+                .parent()
+                .map(|dir| dir.join(requested)),
+            IncludeType::Standard => None,
+        };
+        let candidates = relative
+            .into_iter()
+            .chain(include_dirs.iter().map(|dir| dir.join(requested)))
+            .collect::<Vec<_>>();
+
+        let Some(resolved) = candidates.iter().find(|candidate| candidate.is_file()) else {
+            let searched = candidates
+                .iter()
+                .map(|candidate| format!("\n  {}", candidate.display()))
+                .collect::<String>();
+            return Err(format!(
+                "Cannot find include file `{requested}`, searched:{searched}"
+            ));
+        };
+
+        let content = std::fs::read_to_string(resolved)
+            .map_err(|e| format!("Cannot read `{}`: {e}", resolved.display()))?;
+
+        let resolved_name = resolved.to_string_lossy().into_owned();
+        includes.borrow_mut().push(resolved.clone());
+
+        Ok(ResolvedInclude {
+            resolved_name,
+            content,
+        })
+    });
+
+    let artifact = match compiler.compile_into_spirv(
+        shader_source,
+        shader_kind,
+        &absolute_path.to_string_lossy(),
+        &args.entry_point(),
+        Some(&compile_options),
+    ) {
+        Ok(artifact) => artifact,
+        Err(shaderc::Error::CompilationError(_, messages)) => {
+            if emit_shader_diagnostics(args.path, &messages) == 0 {
+                emit_error!(args.path, "Failed to compile shader");
+            }
+            return Err(TokenStream2::new());
+        }
+        Err(e) => {
+            return Err(syn::Error::new(
+                args.path.span(),
+                format!("Failed to compile shader: {e}"),
+            )
+            .to_compile_error());
+        }
+    };
+
+    let warnings = artifact.get_warning_messages();
+    emit_shader_diagnostics(args.path, &warnings);
+
+    abort_if_dirty();
+
+    let mut includes = includes.take();
+    includes.sort();
+    includes.dedup();
+
+    Ok((artifact.as_binary().to_vec(), includes, warnings))
+}
+
+pub(crate) fn parse_shader_kind(kind: &str) -> Option<ShaderKind> {
+    let kind = match kind {
+        "Vertex" => ShaderKind::Vertex,
+        "Fragment" => ShaderKind::Fragment,
+        "Compute" => ShaderKind::Compute,
+        "Geometry" => ShaderKind::Geometry,
+        "TessControl" => ShaderKind::TessControl,
+        "TessEvaluation" => ShaderKind::TessEvaluation,
+        "RayGeneration" => ShaderKind::RayGeneration,
+        "AnyHit" => ShaderKind::AnyHit,
+        "ClosestHit" => ShaderKind::ClosestHit,
+        "Miss" => ShaderKind::Miss,
+        "Intersection" => ShaderKind::Intersection,
+        "Callable" => ShaderKind::Callable,
+        "Task" => ShaderKind::Task,
+        "Mesh" => ShaderKind::Mesh,
+        "InferFromSource" => ShaderKind::InferFromSource,
+        _ => return None,
+    };
+
+    Some(kind)
+}
+
+/// Stage of the entry point of a shader of this kind, `None` when inferred
+/// from the source.
+fn shader_kind_stage(kind: ShaderKind) -> Option<vk::ShaderStageFlags> {
+    let stage = match kind {
+        ShaderKind::Vertex | ShaderKind::DefaultVertex => vk::ShaderStageFlags::VERTEX,
+        ShaderKind::Fragment | ShaderKind::DefaultFragment => vk::ShaderStageFlags::FRAGMENT,
+        ShaderKind::Compute | ShaderKind::DefaultCompute => vk::ShaderStageFlags::COMPUTE,
+        ShaderKind::Geometry | ShaderKind::DefaultGeometry => vk::ShaderStageFlags::GEOMETRY,
+        ShaderKind::TessControl | ShaderKind::DefaultTessControl => {
+            vk::ShaderStageFlags::TESSELLATION_CONTROL
+        }
+        ShaderKind::TessEvaluation | ShaderKind::DefaultTessEvaluation => {
+            vk::ShaderStageFlags::TESSELLATION_EVALUATION
+        }
+        ShaderKind::RayGeneration | ShaderKind::DefaultRayGeneration => {
+            vk::ShaderStageFlags::RAYGEN_KHR
+        }
+        ShaderKind::AnyHit | ShaderKind::DefaultAnyHit => vk::ShaderStageFlags::ANY_HIT_KHR,
+        ShaderKind::ClosestHit | ShaderKind::DefaultClosestHit => {
+            vk::ShaderStageFlags::CLOSEST_HIT_KHR
+        }
+        ShaderKind::Miss | ShaderKind::DefaultMiss => vk::ShaderStageFlags::MISS_KHR,
+        ShaderKind::Intersection | ShaderKind::DefaultIntersection => {
+            vk::ShaderStageFlags::INTERSECTION_KHR
+        }
+        ShaderKind::Callable | ShaderKind::DefaultCallable => vk::ShaderStageFlags::CALLABLE_KHR,
+        ShaderKind::Task | ShaderKind::DefaultTask => vk::ShaderStageFlags::TASK_EXT,
+        ShaderKind::Mesh | ShaderKind::DefaultMesh => vk::ShaderStageFlags::MESH_EXT,
+        _ => return None,
+    };
+
+    Some(stage)
+}
+
+/// Infer the shader kind from conventional extensions, like `add.comp` or
+/// `add.comp.glsl`.
+pub(crate) fn shader_kind_from_path(path: &Path) -> Option<ShaderKind> {
+    let file_name = path.file_name()?.to_str()?;
+
+    let mut extensions = file_name.rsplit('.');
+    let mut extension = extensions.next()?;
+    if matches!(extension, "glsl" | "hlsl") {
+        extension = extensions.next()?;
+    }
+
+    let kind = match extension {
+        "vert" => ShaderKind::Vertex,
+        "frag" => ShaderKind::Fragment,
+        "comp" => ShaderKind::Compute,
+        "geom" => ShaderKind::Geometry,
+        "tesc" => ShaderKind::TessControl,
+        "tese" => ShaderKind::TessEvaluation,
+        "rgen" => ShaderKind::RayGeneration,
+        "rahit" => ShaderKind::AnyHit,
+        "rchit" => ShaderKind::ClosestHit,
+        "rmiss" => ShaderKind::Miss,
+        "rint" => ShaderKind::Intersection,
+        "rcall" => ShaderKind::Callable,
+        "task" => ShaderKind::Task,
+        "mesh" => ShaderKind::Mesh,
+        _ => return None,
+    };
+
+    Some(kind)
+}
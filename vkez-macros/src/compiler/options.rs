@@ -0,0 +1,294 @@
+use std::path::Path;
+
+use proc_macro2::TokenStream as TokenStream2;
+use proc_macro_error::emit_error;
+use quote::quote;
+use shaderc::{
+    CompileOptions, EnvVersion, OptimizationLevel, ResourceKind, SourceLanguage, SpirvVersion,
+    TargetEnv,
+};
+use structmeta::{NameArgs, StructMeta};
+use syn::{LitBool, LitInt, LitStr};
+
+const TARGET_ENV_VAR: &str = "VKEZ_SHADER_TARGET";
+const SPIRV_ENV_VAR: &str = "VKEZ_SHADER_SPIRV";
+const OPTIMIZE_ENV_VAR: &str = "VKEZ_SHADER_OPTIMIZE";
+const DEBUG_INFO_ENV_VAR: &str = "VKEZ_SHADER_DEBUG_INFO";
+
+/// Added to the HLSL register numbers of each register class to get the
+/// binding numbers, like `register_shift(t = 16, s = 32)`.
+#[derive(StructMeta, Default, Debug, Clone)]
+pub(crate) struct HlslRegisterShift {
+    /// Constant buffers
+    pub b: Option<LitInt>,
+    /// Shader resource views
+    pub t: Option<LitInt>,
+    /// Samplers
+    pub s: Option<LitInt>,
+    /// Unordered access views
+    pub u: Option<LitInt>,
+}
+
+/// Compile options as written in the attributes. Missing options fall back to
+/// the `VKEZ_SHADER_*` environment variables, then to the defaults.
+#[derive(Default, Debug, Clone)]
+pub(crate) struct ShaderCompileOptions {
+    /// `"glsl"`, `"hlsl"` or `"spirv"`, inferred from the `.hlsl` and `.spv`
+    /// extensions by default. Compile options are ignored for SPIR-V.
+    pub lang: Option<LitStr>,
+    /// `"vulkan1.0"` to `"vulkan1.3"`, defaults to `"vulkan1.1"`.
+    pub target: Option<LitStr>,
+    /// `"1.0"` to `"1.6"`, defaults to the highest version of the target.
+    pub spirv: Option<LitStr>,
+    /// `"zero"`, `"size"` or `"performance"`, defaults to `"performance"`.
+    pub optimize: Option<LitStr>,
+    /// Defaults to `true` when the crate is built with debug assertions.
+    pub debug_info: Option<LitBool>,
+    /// Give a binding to HLSL resources without an explicit register.
+    pub auto_bind: Option<LitBool>,
+    pub register_shift: Option<NameArgs<HlslRegisterShift>>,
+}
+
+impl ShaderCompileOptions {
+    /// Options of `self` take precedence over `fallback`.
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            lang: self.lang.or(fallback.lang),
+            target: self.target.or(fallback.target),
+            spirv: self.spirv.or(fallback.spirv),
+            optimize: self.optimize.or(fallback.optimize),
+            debug_info: self.debug_info.or(fallback.debug_info),
+            auto_bind: self.auto_bind.or(fallback.auto_bind),
+            register_shift: self.register_shift.or(fallback.register_shift),
+        }
+    }
+
+    /// Whether the shader at `path` is a SPIR-V binary rather than a source.
+    pub fn is_precompiled(&self, path: &Path) -> bool {
+        match self.lang.as_ref() {
+            Some(lang) => lang.value() == "spirv",
+            None => path.extension().is_some_and(|ext| ext == "spv"),
+        }
+    }
+
+    /// Source language of the shader at `path`.
+    pub fn source_language(&self, path: &Path) -> SourceLanguage {
+        match self.lang.as_ref() {
+            Some(lang) => match lang.value().as_str() {
+                "glsl" => SourceLanguage::GLSL,
+                "hlsl" => SourceLanguage::HLSL,
+                _ => {
+                    emit_error!(lang, "Unknown shader language"; help = "Expected glsl, hlsl or spirv");
+                    SourceLanguage::GLSL
+                }
+            },
+            None if path.extension().is_some_and(|ext| ext == "hlsl") => SourceLanguage::HLSL,
+            None => SourceLanguage::GLSL,
+        }
+    }
+
+    /// Emits an error for every invalid option, `span_source` is used for
+    /// errors coming from the environment.
+    pub fn apply(
+        &self, options: &mut CompileOptions, language: SourceLanguage, span_source: &LitStr,
+    ) {
+        options.set_source_language(language);
+        if language == SourceLanguage::HLSL {
+            self.apply_hlsl(options);
+        } else {
+            if let Some(auto_bind) = self.auto_bind.as_ref() {
+                emit_error!(auto_bind, "`auto_bind` is only supported by HLSL shaders");
+            }
+            if let Some(register_shift) = self.register_shift.as_ref() {
+                emit_error!(
+                    register_shift.name_span,
+                    "`register_shift` is only supported by HLSL shaders"
+                );
+            }
+        }
+
+        let target = option_or_env(self.target.as_ref(), TARGET_ENV_VAR, span_source)
+            .map(|(target, span)| {
+                parse_target(&target).unwrap_or_else(|| {
+                    emit_error!(span, "Unknown target `{}`", target; help = "Expected one of vulkan1.0, vulkan1.1, vulkan1.2 or vulkan1.3");
+                    EnvVersion::Vulkan1_1
+                })
+            })
+            .unwrap_or(EnvVersion::Vulkan1_1);
+        options.set_target_env(TargetEnv::Vulkan, target as _);
+
+        if let Some((spirv, span)) = option_or_env(self.spirv.as_ref(), SPIRV_ENV_VAR, span_source)
+        {
+            match parse_spirv_version(&spirv) {
+                Some(version) => options.set_target_spirv(version),
+                None => {
+                    emit_error!(span, "Unknown SPIR-V version `{}`", spirv; help = "Expected a version from 1.0 to 1.6")
+                }
+            }
+        }
+
+        let optimize = option_or_env(self.optimize.as_ref(), OPTIMIZE_ENV_VAR, span_source)
+            .map(|(optimize, span)| {
+                parse_optimization_level(&optimize).unwrap_or_else(|| {
+                    emit_error!(span, "Unknown optimization level `{}`", optimize; help = "Expected one of zero, size or performance");
+                    OptimizationLevel::Performance
+                })
+            })
+            .unwrap_or(OptimizationLevel::Performance);
+        options.set_optimization_level(optimize);
+
+        let debug_info = match self.debug_info.as_ref() {
+            Some(debug_info) => debug_info.value(),
+            None => match std::env::var(DEBUG_INFO_ENV_VAR) {
+                Ok(value) => match value.as_str() {
+                    "1" | "true" => true,
+                    "0" | "false" => false,
+                    _ => {
+                        emit_error!(span_source, "Invalid value `{}` for {}", value, DEBUG_INFO_ENV_VAR; help = "Expected true or false");
+                        false
+                    }
+                },
+                // Proc macros follow the profile of the crate being built
+                Err(_) => cfg!(debug_assertions),
+            },
+        };
+        if debug_info {
+            options.set_generate_debug_info();
+        }
+    }
+
+    /// Everything `apply` reads, including the environment, for the key of
+    /// the shader cache.
+    pub fn cache_key(&self) -> String {
+        let option_or_env = |option: Option<&LitStr>, env_var| {
+            option
+                .map(LitStr::value)
+                .or_else(|| std::env::var(env_var).ok())
+        };
+        let register_shift = self.register_shift.as_ref().map(|shift| {
+            [&shift.args.b, &shift.args.t, &shift.args.s, &shift.args.u]
+                .map(|shift| shift.as_ref().map(LitInt::to_string))
+        });
+
+        format!(
+            "{:?}",
+            (
+                self.lang.as_ref().map(LitStr::value),
+                option_or_env(self.target.as_ref(), TARGET_ENV_VAR),
+                option_or_env(self.spirv.as_ref(), SPIRV_ENV_VAR),
+                option_or_env(self.optimize.as_ref(), OPTIMIZE_ENV_VAR),
+                self.debug_info.as_ref().map(LitBool::value),
+                std::env::var(DEBUG_INFO_ENV_VAR).ok(),
+                cfg!(debug_assertions),
+                self.auto_bind.as_ref().map(LitBool::value),
+                register_shift,
+            )
+        )
+    }
+
+    fn apply_hlsl(&self, options: &mut CompileOptions) {
+        // Lay out buffers with the HLSL packing rules, and bind resources from
+        // their `register(...)`
+        options.set_hlsl_offsets(true);
+        options.set_hlsl_io_mapping(true);
+
+        if let Some(auto_bind) = self.auto_bind.as_ref() {
+            options.set_auto_bind_uniforms(auto_bind.value());
+        }
+
+        let Some(NameArgs { args: shift, .. }) = self.register_shift.as_ref() else {
+            return;
+        };
+
+        let kinds: [(&Option<LitInt>, &[ResourceKind]); 4] = [
+            (&shift.b, &[ResourceKind::Buffer]),
+            (&shift.t, &[ResourceKind::Texture]),
+            (&shift.s, &[ResourceKind::Sampler]),
+            (&shift.u, &[
+                ResourceKind::UnorderedAccessView,
+                ResourceKind::Image,
+                ResourceKind::StorageBuffer,
+            ]),
+        ];
+
+        for (shift, resource_kinds) in kinds {
+            let Some(shift) = shift else {
+                continue;
+            };
+
+            match shift.base10_parse::<u32>() {
+                Ok(base) => {
+                    for kind in resource_kinds {
+                        options.set_binding_base(*kind, base);
+                    }
+                }
+                Err(e) => emit_error!(shift, "{}", e),
+            }
+        }
+    }
+}
+
+/// Reads every variable of the compile options with `option_env!` in the
+/// generated code, so that cargo rebuilds the crate when one of them changes.
+/// Proc macros can only track them this way until `tracked_env` is stable.
+pub(crate) fn env_tracking_tokens() -> TokenStream2 {
+    let env_vars = [
+        TARGET_ENV_VAR,
+        SPIRV_ENV_VAR,
+        OPTIMIZE_ENV_VAR,
+        DEBUG_INFO_ENV_VAR,
+    ];
+
+    quote!(#(const _: ::core::option::Option<&str> = ::core::option_env!(#env_vars);)*)
+}
+
+/// Value of the attribute, or of the environment variable, with the span to
+/// report errors at.
+fn option_or_env<'a>(
+    option: Option<&'a LitStr>, env_var: &str, span_source: &'a LitStr,
+) -> Option<(String, &'a LitStr)> {
+    match option {
+        Some(option) => Some((option.value(), option)),
+        None => std::env::var(env_var)
+            .ok()
+            .map(|value| (value, span_source)),
+    }
+}
+
+fn parse_target(target: &str) -> Option<EnvVersion> {
+    let target = match target {
+        "vulkan1.0" => EnvVersion::Vulkan1_0,
+        "vulkan1.1" => EnvVersion::Vulkan1_1,
+        "vulkan1.2" => EnvVersion::Vulkan1_2,
+        "vulkan1.3" => EnvVersion::Vulkan1_3,
+        _ => return None,
+    };
+
+    Some(target)
+}
+
+fn parse_spirv_version(version: &str) -> Option<SpirvVersion> {
+    let version = match version {
+        "1.0" => SpirvVersion::V1_0,
+        "1.1" => SpirvVersion::V1_1,
+        "1.2" => SpirvVersion::V1_2,
+        "1.3" => SpirvVersion::V1_3,
+        "1.4" => SpirvVersion::V1_4,
+        "1.5" => SpirvVersion::V1_5,
+        "1.6" => SpirvVersion::V1_6,
+        _ => return None,
+    };
+
+    Some(version)
+}
+
+fn parse_optimization_level(level: &str) -> Option<OptimizationLevel> {
+    let level = match level {
+        "zero" => OptimizationLevel::Zero,
+        "size" => OptimizationLevel::Size,
+        "performance" => OptimizationLevel::Performance,
+        _ => return None,
+    };
+
+    Some(level)
+}
@@ -0,0 +1,620 @@
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use structmeta::{NameArgs, StructMeta};
+use syn::{Data, DeriveInput, Fields, Ident, LitInt};
+use vkez_core::ash::vk;
+
+use crate::{
+    reflect::DescriptorBinding,
+    sampler::{sampler_create_info_tokens, SamplerArgs},
+    tokens::{
+        binding_flags_tokens, descriptor_type_tokens, parse_stage, snake_case_ident,
+        stage_flags_tokens,
+    },
+};
+
+const DESCRIPTOR_KINDS: &[(&str, vk::DescriptorType)] = &[
+    ("sampler", vk::DescriptorType::SAMPLER),
+    (
+        "combined_image_sampler",
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+    ),
+    ("sampled_image", vk::DescriptorType::SAMPLED_IMAGE),
+    ("storage_image", vk::DescriptorType::STORAGE_IMAGE),
+    (
+        "uniform_texel_buffer",
+        vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+    ),
+    (
+        "storage_texel_buffer",
+        vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+    ),
+    ("uniform_buffer", vk::DescriptorType::UNIFORM_BUFFER),
+    ("storage_buffer", vk::DescriptorType::STORAGE_BUFFER),
+    (
+        "uniform_buffer_dynamic",
+        vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+    ),
+    (
+        "storage_buffer_dynamic",
+        vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+    ),
+    ("input_attachment", vk::DescriptorType::INPUT_ATTACHMENT),
+    (
+        "acceleration_structure",
+        vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+    ),
+];
+
+/// `#[binding(0, storage_buffer, count = 4, stages(VERTEX, FRAGMENT))]`
+#[derive(StructMeta)]
+struct BindingArgs {
+    #[struct_meta(unnamed)]
+    binding: LitInt,
+    #[struct_meta(unnamed)]
+    kind: Ident,
+    /// Defaults to 1, the maximum count with `variable_count`.
+    count: Option<LitInt>,
+    /// `vk::ShaderStageFlags` variants, defaults to `ALL`.
+    stages: Option<Vec<Ident>>,
+    partially_bound: bool,
+    update_after_bind: bool,
+    update_unused_while_pending: bool,
+    /// Only for the last binding, the count is chosen when allocating.
+    variable_count: bool,
+    /// For `sampler` and `combined_image_sampler` bindings.
+    immutable_sampler: Option<NameArgs<SamplerArgs>>,
+}
+
+pub(crate) fn derive_descriptor_set_impl(item: DeriveInput) -> Result<TokenStream2, TokenStream2> {
+    let ident = &item.ident;
+
+    let Data::Struct(data) = &item.data else {
+        return Err(error(
+            ident,
+            "DescriptorSet can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(error(
+            ident,
+            "DescriptorSet can only be derived for structs with named fields",
+        ));
+    };
+    if !item.generics.params.is_empty() {
+        return Err(error(
+            &item.generics,
+            "Generic descriptor sets are not supported",
+        ));
+    }
+
+    let mut bindings = Vec::<DescriptorBinding>::new();
+    let mut fields_by_binding = HashMap::new();
+    for field in &fields.named {
+        let field_ident = field.ident.as_ref().unwrap();
+
+        let mut binding_attrs = field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("binding"));
+        let Some(attr) = binding_attrs.next() else {
+            return Err(error(
+                field_ident,
+                "Missing binding, add `#[binding(N, kind)]`",
+            ));
+        };
+        if let Some(duplicate) = binding_attrs.next() {
+            return Err(error(duplicate, "Duplicate `binding` attribute"));
+        }
+        let args = attr
+            .parse_args::<BindingArgs>()
+            .map_err(|e| e.to_compile_error())?;
+
+        let binding = args
+            .binding
+            .base10_parse::<u32>()
+            .map_err(|e| e.to_compile_error())?;
+        if let Some(previous) = fields_by_binding.insert(binding, field_ident) {
+            return Err(error(
+                &args.binding,
+                &format!("Binding {binding} is already used by `{previous}`"),
+            ));
+        }
+
+        let kind = args.kind.to_string();
+        let Some(&(_, descriptor_type)) = DESCRIPTOR_KINDS.iter().find(|(name, _)| *name == kind)
+        else {
+            let kinds = DESCRIPTOR_KINDS
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>();
+            return Err(error(
+                &args.kind,
+                &format!(
+                    "Unknown descriptor kind, expected one of {}",
+                    kinds.join(", ")
+                ),
+            ));
+        };
+
+        let count = match args.count.as_ref() {
+            Some(count) => match count.base10_parse::<u32>() {
+                Ok(0) => return Err(error(count, "Descriptor count must be at least 1")),
+                Ok(count) => count,
+                Err(e) => return Err(e.to_compile_error()),
+            },
+            None => 1,
+        };
+
+        let stages = match args.stages.as_ref() {
+            Some(names) => {
+                let mut stages = vk::ShaderStageFlags::empty();
+                for name in names {
+                    let Some(stage) = parse_stage(&name.to_string()) else {
+                        return Err(error(
+                            name,
+                            "Unknown shader stage, see vk::ShaderStageFlags",
+                        ));
+                    };
+                    stages |= stage;
+                }
+                stages
+            }
+            None => vk::ShaderStageFlags::ALL,
+        };
+
+        let flags = [
+            (
+                args.partially_bound,
+                vk::DescriptorBindingFlags::PARTIALLY_BOUND,
+            ),
+            (
+                args.update_after_bind,
+                vk::DescriptorBindingFlags::UPDATE_AFTER_BIND,
+            ),
+            (
+                args.update_unused_while_pending,
+                vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING,
+            ),
+            (
+                args.variable_count,
+                vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT,
+            ),
+        ]
+        .into_iter()
+        .filter(|(present, _)| *present)
+        .fold(vk::DescriptorBindingFlags::empty(), |flags, (_, flag)| {
+            flags | flag
+        });
+
+        let immutable_sampler = args
+            .immutable_sampler
+            .as_ref()
+            .map(|sampler| sampler.args.create_info())
+            .transpose()?;
+
+        bindings.push(DescriptorBinding {
+            name: field_ident.to_string(),
+            set: 0,
+            binding,
+            descriptor_type,
+            count,
+            stages,
+            flags,
+            immutable_sampler,
+        });
+    }
+
+    bindings.sort_by_key(|b| b.binding);
+    check_bindings(&bindings).map_err(|e| error(ident, &e))?;
+    Ok(gen_descriptor_set_info(ident, &bindings))
+}
+
+/// Declarations that Vulkan rejects regardless of the enabled features.
+pub(crate) fn check_bindings(bindings: &[DescriptorBinding]) -> Result<(), String> {
+    let last_binding = bindings.iter().map(|b| b.binding).max();
+    for b in bindings {
+        if b.flags
+            .contains(vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT)
+            && Some(b.binding) != last_binding
+        {
+            return Err(format!(
+                "Only the last binding can have a variable count, not `{}` at binding {}",
+                b.name, b.binding
+            ));
+        }
+
+        if b.flags
+            .contains(vk::DescriptorBindingFlags::UPDATE_AFTER_BIND)
+            && matches!(
+                b.descriptor_type,
+                vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+                    | vk::DescriptorType::STORAGE_BUFFER_DYNAMIC
+            )
+        {
+            return Err(format!(
+                "Dynamic buffers can't be updated after bind, `{}` at binding {}",
+                b.name, b.binding
+            ));
+        }
+
+        if b.immutable_sampler.is_some()
+            && !matches!(
+                b.descriptor_type,
+                vk::DescriptorType::SAMPLER | vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+            )
+        {
+            return Err(format!(
+                "Only samplers and combined image samplers can have immutable samplers, not `{}` \
+                 at binding {}",
+                b.name, b.binding
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn error(tokens: impl quote::ToTokens, message: &str) -> TokenStream2 {
+    syn::Error::new_spanned(tokens, message).to_compile_error()
+}
+
+pub(crate) fn gen_layout_binding(b: &DescriptorBinding) -> TokenStream2 {
+    let binding = b.binding;
+    let descriptor_type = descriptor_type_tokens(b.descriptor_type);
+    let count = b.count;
+    let stages = stage_flags_tokens(b.stages);
+
+    quote! {
+        ::vkez_core::ash::vk::DescriptorSetLayoutBinding {
+            binding: #binding,
+            descriptor_type: #descriptor_type,
+            descriptor_count: #count,
+            stage_flags: #stages,
+            p_immutable_samplers: ::std::ptr::null(),
+        }
+    }
+}
+
+pub(crate) fn immutable_samplers_tokens(b: &DescriptorBinding) -> Option<TokenStream2> {
+    let binding = b.binding;
+    let count = b.count as usize;
+    let create_info = sampler_create_info_tokens(b.immutable_sampler.as_ref()?);
+
+    Some(quote! {
+        ::vkez_core::descriptor_sets::ImmutableSamplers {
+            binding: #binding,
+            create_infos: &[#create_info; #count],
+        }
+    })
+}
+
+pub(crate) fn gen_descriptor_set_info(
+    ident: &Ident, bindings: &[DescriptorBinding],
+) -> TokenStream2 {
+    let layout_bindings = bindings.iter().map(gen_layout_binding);
+
+    // Merge the bindings by descriptor type
+    let mut pool_sizes = Vec::<(vk::DescriptorType, u32)>::new();
+    for b in bindings {
+        match pool_sizes
+            .iter_mut()
+            .find(|(ty, _)| *ty == b.descriptor_type)
+        {
+            Some((_, count)) => *count += b.count,
+            None => pool_sizes.push((b.descriptor_type, b.count)),
+        }
+    }
+    let pool_sizes = pool_sizes.into_iter().map(|(ty, count)| {
+        let ty = descriptor_type_tokens(ty);
+        quote! {
+            ::vkez_core::ash::vk::DescriptorPoolSize {
+                ty: #ty,
+                descriptor_count: #count,
+            }
+        }
+    });
+
+    let writer = gen_descriptor_set_writer(ident, bindings);
+
+    let layout_flags = if bindings.iter().any(|b| {
+        b.flags
+            .contains(vk::DescriptorBindingFlags::UPDATE_AFTER_BIND)
+    }) {
+        quote!(::vkez_core::ash::vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+    } else {
+        quote!(::vkez_core::ash::vk::DescriptorSetLayoutCreateFlags::empty())
+    };
+
+    let immutable_samplers = bindings.iter().any(|b| b.immutable_sampler.is_some()).then(|| {
+        let immutable_samplers = bindings.iter().filter_map(immutable_samplers_tokens);
+        quote! {
+            const IMMUTABLE_SAMPLERS: &'static [::vkez_core::descriptor_sets::ImmutableSamplers] = &[
+                #(#immutable_samplers),*
+            ];
+        }
+    });
+
+    let binding_flags = bindings.iter().any(|b| !b.flags.is_empty()).then(|| {
+        let flags = bindings.iter().map(|b| binding_flags_tokens(b.flags));
+        quote! {
+            const BINDING_FLAGS: &'static [::vkez_core::ash::vk::DescriptorBindingFlags] = &[
+                #(#flags),*
+            ];
+        }
+    });
+
+    quote! {
+        #writer
+
+        unsafe impl ::vkez_core::descriptor_sets::RawDescriptorSetInfo for #ident {
+            const LAYOUT_BINDINGS_CREATE_INFO: &'static [::vkez_core::ash::vk::DescriptorSetLayoutBinding] = &[
+                #(#layout_bindings),*
+            ];
+
+            const LAYOUT_CREATE_INFO: ::vkez_core::ash::vk::DescriptorSetLayoutCreateInfo =
+                ::vkez_core::ash::vk::DescriptorSetLayoutCreateInfo {
+                    s_type: ::vkez_core::ash::vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+                    p_next: ::std::ptr::null(),
+                    flags: #layout_flags,
+                    binding_count: Self::LAYOUT_BINDINGS_CREATE_INFO.len() as _,
+                    p_bindings: Self::LAYOUT_BINDINGS_CREATE_INFO.as_ptr(),
+                };
+
+            #binding_flags
+
+            #immutable_samplers
+
+            const POOL_SIZES_FOR_ONE: &'static [::vkez_core::ash::vk::DescriptorPoolSize] = &[
+                #(#pool_sizes),*
+            ];
+        }
+    }
+}
+
+/// `<Set>Writer`, with a method per binding taking the resources to bind and a
+/// `write` method updating the set with all of them at once.
+fn gen_descriptor_set_writer(ident: &Ident, bindings: &[DescriptorBinding]) -> TokenStream2 {
+    let writer_ident = format_ident!("{}Writer", ident);
+
+    let mut used = HashSet::new();
+    let mut fields = Vec::new();
+    let mut methods = Vec::new();
+    let mut writes = Vec::new();
+    for b in bindings {
+        // Writes to immutable samplers are ignored
+        if b.descriptor_type == vk::DescriptorType::SAMPLER && b.immutable_sampler.is_some() {
+            continue;
+        }
+
+        let method = writer_method_ident(b, &mut used);
+        let binding = b.binding;
+        let name = &b.name;
+        let descriptor_type = descriptor_type_tokens(b.descriptor_type);
+
+        let WriterArgument {
+            element,
+            name: param,
+            ty: arg,
+            convert,
+        } = writer_argument(b.descriptor_type);
+        let count = b.count as usize;
+        let (field, param, param_ty, value) = if b
+            .flags
+            .contains(vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT)
+        {
+            let param = format_ident!("{}s", param);
+            let value = match convert {
+                Some(convert) => quote!(#param.into_iter().map(#convert).collect()),
+                None => quote!(#param.into_iter().collect()),
+            };
+            (
+                quote!(::std::vec::Vec<#element>),
+                param,
+                quote!(impl ::std::iter::IntoIterator<Item = #arg>),
+                value,
+            )
+        } else if count == 1 {
+            let value = match convert {
+                Some(convert) => quote!([#param].map(#convert)),
+                None => quote!([#param]),
+            };
+            (quote!([#element; 1]), param, arg, value)
+        } else {
+            let param = format_ident!("{}s", param);
+            let value = match convert {
+                Some(convert) => quote!(#param.map(#convert)),
+                None => quote!(#param),
+            };
+            (
+                quote!([#element; #count]),
+                param,
+                quote!([#arg; #count]),
+                value,
+            )
+        };
+
+        fields.push(quote!(#method: ::std::option::Option<#field>));
+        methods.push(quote! {
+            #[inline]
+            pub fn #method(&mut self, #param: #param_ty) -> &mut Self {
+                self.#method = ::std::option::Option::Some(#value);
+                self
+            }
+        });
+
+        // Partially bound bindings may be left unset
+        let resources = if b
+            .flags
+            .contains(vk::DescriptorBindingFlags::PARTIALLY_BOUND)
+        {
+            quote!(self.#method.as_ref())
+        } else {
+            quote! {
+                ::std::option::Option::Some(self.#method.as_ref().ok_or(
+                    ::vkez_core::descriptor_sets::UnsetBinding {
+                        binding: #binding,
+                        name: #name,
+                    },
+                )?)
+            }
+        };
+
+        let write = quote! {
+            ::vkez_core::ash::vk::WriteDescriptorSet::builder()
+                .dst_set(set)
+                .dst_binding(#binding)
+                .descriptor_type(#descriptor_type)
+        };
+        // Has to outlive the update
+        let extension = format_ident!("{}_extension", method);
+        let declare_extension = (b.descriptor_type
+            == vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+            .then(|| quote!(let #extension;));
+
+        let push = match b.descriptor_type {
+            vk::DescriptorType::UNIFORM_BUFFER
+            | vk::DescriptorType::STORAGE_BUFFER
+            | vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+            | vk::DescriptorType::STORAGE_BUFFER_DYNAMIC => {
+                quote!(writes.push(#write.buffer_info(#method).build());)
+            }
+            vk::DescriptorType::UNIFORM_TEXEL_BUFFER | vk::DescriptorType::STORAGE_TEXEL_BUFFER => {
+                quote!(writes.push(#write.texel_buffer_view(#method).build());)
+            }
+            // The count isn't inferred from the extension structure
+            vk::DescriptorType::ACCELERATION_STRUCTURE_KHR => quote! {
+                #extension = ::vkez_core::ash::vk::WriteDescriptorSetAccelerationStructureKHR::builder()
+                    .acceleration_structures(#method)
+                    .build();
+                let mut write = #write.build();
+                write.p_next = &#extension as *const _ as _;
+                write.descriptor_count = #method.len() as u32;
+                writes.push(write);
+            },
+            _ => quote!(writes.push(#write.image_info(#method).build());),
+        };
+
+        // Writes of no descriptors are invalid
+        writes.push(quote! {
+            #declare_extension
+            if let ::std::option::Option::Some(#method) =
+                #resources.map(|resources| &resources[..]).filter(|resources| !resources.is_empty())
+            {
+                #push
+            }
+        });
+    }
+
+    quote! {
+        #[derive(Debug, Default, Clone)]
+        pub struct #writer_ident {
+            #(#fields,)*
+        }
+
+        impl #writer_ident {
+            #(#methods)*
+
+            /// Updates every binding of `set` in a single call, fails if a
+            /// binding that isn't partially bound was not set.
+            pub unsafe fn write(
+                &self,
+                device: &::vkez_core::ash::Device,
+                set: ::vkez_core::ash::vk::DescriptorSet,
+            ) -> ::std::result::Result<(), ::vkez_core::descriptor_sets::UnsetBinding> {
+                let mut writes = ::std::vec::Vec::new();
+                #(#writes)*
+                device.update_descriptor_sets(&writes, &[]);
+                ::std::result::Result::Ok(())
+            }
+        }
+
+        impl #ident {
+            #[inline]
+            pub fn writer() -> #writer_ident {
+                ::std::default::Default::default()
+            }
+        }
+    }
+}
+
+/// Snake case name of the binding, or `binding{N}` when it has no usable name.
+fn writer_method_ident(b: &DescriptorBinding, used: &mut HashSet<String>) -> Ident {
+    let ident = syn::parse_str::<Ident>(&b.name)
+        .map(|ident| snake_case_ident(&ident).to_string())
+        .and_then(|name| syn::parse_str::<Ident>(&name))
+        .ok()
+        // Names used by `write` itself
+        .filter(|ident| !["write", "writes", "device", "set"].contains(&ident.to_string().as_str()))
+        .filter(|ident| !used.contains(&ident.to_string()))
+        .unwrap_or_else(|| format_ident!("binding{}", b.binding));
+
+    used.insert(ident.to_string());
+    ident
+}
+
+struct WriterArgument {
+    /// Stored by the writer and passed to the update.
+    element: TokenStream2,
+    /// Of the method parameter, pluralized for arrays.
+    name: Ident,
+    ty: TokenStream2,
+    /// From `ty` to `element` when they differ.
+    convert: Option<TokenStream2>,
+}
+
+fn writer_argument(descriptor_type: vk::DescriptorType) -> WriterArgument {
+    let argument = |element, name, ty, convert| WriterArgument {
+        element,
+        name: format_ident!("{}", name),
+        ty,
+        convert,
+    };
+
+    match descriptor_type {
+        vk::DescriptorType::UNIFORM_BUFFER
+        | vk::DescriptorType::STORAGE_BUFFER
+        | vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+        | vk::DescriptorType::STORAGE_BUFFER_DYNAMIC => argument(
+            quote!(::vkez_core::ash::vk::DescriptorBufferInfo),
+            "buffer",
+            quote!(impl ::vkez_core::descriptor_sets::IntoDescriptorBufferInfo),
+            Some(quote!(
+                ::vkez_core::descriptor_sets::IntoDescriptorBufferInfo::into_descriptor_buffer_info
+            )),
+        ),
+        vk::DescriptorType::SAMPLER => argument(
+            quote!(::vkez_core::ash::vk::DescriptorImageInfo),
+            "sampler",
+            quote!(::vkez_core::ash::vk::Sampler),
+            Some(quote!(|sampler| {
+                ::vkez_core::ash::vk::DescriptorImageInfo {
+                    sampler,
+                    ..::std::default::Default::default()
+                }
+            })),
+        ),
+        vk::DescriptorType::UNIFORM_TEXEL_BUFFER | vk::DescriptorType::STORAGE_TEXEL_BUFFER => {
+            argument(
+                quote!(::vkez_core::ash::vk::BufferView),
+                "view",
+                quote!(::vkez_core::ash::vk::BufferView),
+                None,
+            )
+        }
+        vk::DescriptorType::ACCELERATION_STRUCTURE_KHR => argument(
+            quote!(::vkez_core::ash::vk::AccelerationStructureKHR),
+            "acceleration_structure",
+            quote!(::vkez_core::ash::vk::AccelerationStructureKHR),
+            None,
+        ),
+        _ => argument(
+            quote!(::vkez_core::ash::vk::DescriptorImageInfo),
+            "image",
+            quote!(::vkez_core::ash::vk::DescriptorImageInfo),
+            None,
+        ),
+    }
+}
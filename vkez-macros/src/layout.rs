@@ -0,0 +1,613 @@
+//! Rust `#[repr(C)]` types matching reflected block layouts, with explicit
+//! padding and compile-time layout assertions.
+
+use std::collections::HashMap;
+
+use proc_macro2::{Literal, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::Ident;
+
+use crate::{
+    reflect::{
+        LocalSize, MemberLayout, PushConstantBlock, ScalarType, ShaderReflection,
+        SpecializationConstant, StructLayout, TypeLayout,
+    },
+    tokens::stage_flags_tokens,
+    vertex::vertex_inputs_tokens,
+};
+
+#[derive(Default)]
+pub(crate) struct LayoutTypes {
+    /// Rust name of every generated struct, by SPIR-V type id.
+    names: HashMap<u32, Ident>,
+    pub items: Vec<TokenStream2>,
+}
+
+impl LayoutTypes {
+    /// Generates `layout` and the structs it contains, `name` overrides the
+    /// name from the shader.
+    pub fn gen_struct(
+        &mut self, layout: &StructLayout, name: Option<Ident>,
+    ) -> Result<Ident, String> {
+        self.gen_struct_inner(layout, name, false)
+    }
+
+    /// Generates the type of a buffer block. A trailing runtime-sized array is
+    /// left out of the struct, and described by a `RuntimeArrayBlock` impl.
+    pub fn gen_block(&mut self, layout: &StructLayout) -> Result<Ident, String> {
+        self.gen_struct_inner(layout, None, true)
+    }
+
+    fn gen_struct_inner(
+        &mut self, layout: &StructLayout, name: Option<Ident>, allow_runtime_array: bool,
+    ) -> Result<Ident, String> {
+        if let Some(ident) = self.names.get(&layout.id) {
+            return Ok(ident.clone());
+        }
+
+        let ident = name.unwrap_or_else(|| self.struct_ident(&layout.name, layout.id));
+        self.names.insert(layout.id, ident.clone());
+
+        let mut members = layout.members.as_slice();
+        let mut runtime_array = None;
+        if let Some((
+            last @ MemberLayout {
+                ty:
+                    TypeLayout::Array {
+                        element,
+                        length: None,
+                        stride,
+                    },
+                ..
+            },
+            rest,
+        )) = members.split_last()
+        {
+            if allow_runtime_array {
+                runtime_array = Some((last, element, *stride));
+                members = rest;
+            }
+        }
+
+        let mut fields = Vec::new();
+        let mut paddings = 0;
+        let mut asserts = Vec::new();
+        let mut end = 0;
+        for (index, member) in members.iter().enumerate() {
+            if member.offset < end {
+                return Err(format!(
+                    "Member `{}` of `{}` overlaps the previous member",
+                    member.name, layout.name
+                ));
+            }
+            if member.offset > end {
+                fields.push(padding_field(&mut paddings, member.offset - end));
+            }
+
+            let field = field_ident(&member.name, index);
+            let ty = self
+                .rust_type(&member.ty)
+                .map_err(|e| format!("{e} (member `{}` of `{}`)", member.name, layout.name))?;
+            let offset = member.offset as usize;
+
+            fields.push(quote!(pub #field: #ty));
+            asserts.push(quote! {
+                ::core::assert!(::core::mem::offset_of!(#ident, #field) == #offset);
+            });
+
+            end = member.offset + rust_size(&member.ty);
+        }
+
+        // Explicit trailing padding, so that the whole struct can be copied
+        // as bytes. The elements of a runtime array start right after it.
+        let size = match runtime_array {
+            Some((member, _, _)) => {
+                if member.offset < end || member.offset % layout.align() != 0 {
+                    return Err(format!(
+                        "Unsupported offset {} for the runtime array `{}` of `{}`",
+                        member.offset, member.name, layout.name
+                    ));
+                }
+                member.offset
+            }
+            None => round_up(end, layout.align()),
+        };
+        if size > end {
+            fields.push(padding_field(&mut paddings, size - end));
+        }
+        let size = size as usize;
+
+        if let Some((member, element, stride)) = runtime_array {
+            let element = self
+                .array_element(element, stride)
+                .map_err(|e| format!("{e} (member `{}` of `{}`)", member.name, layout.name))?;
+            let stride = stride as usize;
+
+            self.items.push(quote! {
+                unsafe impl ::vkez_core::layout::RuntimeArrayBlock for #ident {
+                    type Element = #element;
+                }
+
+                const _: () = ::core::assert!(::core::mem::size_of::<#element>() == #stride);
+            });
+        }
+
+        self.items.push(quote! {
+            #[repr(C)]
+            #[derive(Clone, Copy, Debug, PartialEq)]
+            #[allow(non_snake_case)]
+            pub struct #ident {
+                #(#fields),*
+            }
+
+            impl ::core::default::Default for #ident {
+                #[inline]
+                fn default() -> Self {
+                    // SAFETY: only made of integers and floats
+                    unsafe { ::core::mem::zeroed() }
+                }
+            }
+
+            const _: () = {
+                ::core::assert!(::core::mem::size_of::<#ident>() == #size);
+                #(#asserts)*
+            };
+        });
+
+        Ok(ident)
+    }
+
+    fn rust_type(&mut self, ty: &TypeLayout) -> Result<TokenStream2, String> {
+        let tokens = match ty {
+            TypeLayout::Scalar(scalar) => scalar_tokens(*scalar)?,
+            TypeLayout::Vector { scalar, count } => {
+                let scalar = scalar_tokens(*scalar)?;
+                let count = *count as usize;
+                quote!([#scalar; #count])
+            }
+            // Padding between columns (or rows) is kept as extra elements, so
+            // a std140 `mat3` is a `[[f32; 4]; 3]`
+            TypeLayout::Matrix {
+                scalar,
+                vectors,
+                stride,
+                ..
+            } => {
+                if stride % scalar.size() != 0 {
+                    return Err(format!("Unsupported matrix stride {stride}"));
+                }
+                let count = (stride / scalar.size()) as usize;
+                let vectors = *vectors as usize;
+                let scalar = scalar_tokens(*scalar)?;
+                quote!([[#scalar; #count]; #vectors])
+            }
+            TypeLayout::Array {
+                element,
+                length: Some(length),
+                stride,
+            } => {
+                let element = self.array_element(element, *stride)?;
+                let length = *length as usize;
+                quote!([#element; #length])
+            }
+            TypeLayout::Array { length: None, .. } => {
+                return Err("Runtime-sized arrays are not supported here".to_string());
+            }
+            TypeLayout::Struct(layout) => {
+                let ident = self.gen_struct(layout, None)?;
+                quote!(#ident)
+            }
+        };
+
+        Ok(tokens)
+    }
+
+    /// Type of an array element, padded up to the array stride.
+    fn array_element(&mut self, element: &TypeLayout, stride: u32) -> Result<TokenStream2, String> {
+        let element_size = rust_size(element);
+        if stride < element_size {
+            return Err(format!("Unsupported array stride {stride}"));
+        }
+
+        let element = self.rust_type(element)?;
+        if stride == element_size {
+            Ok(element)
+        } else {
+            let padding = (stride - element_size) as usize;
+            Ok(quote!(::vkez_core::layout::Padded<#element, #padding>))
+        }
+    }
+
+    /// Name of the shader's struct, made unique among the generated ones.
+    fn struct_ident(&self, name: &str, id: u32) -> Ident {
+        // HLSL names look like `type.ConstantBuffer.Light`
+        let name = name.rsplit('.').next().unwrap_or(name);
+        let ident = match syn::parse_str::<Ident>(name) {
+            Ok(ident) => ident,
+            Err(_) => format_ident!("Struct{}", id),
+        };
+
+        if self.names.values().any(|existing| *existing == ident) {
+            format_ident!("{}{}", ident, id)
+        } else {
+            ident
+        }
+    }
+}
+
+/// Size of the Rust type, with the trailing padding of structs.
+fn rust_size(ty: &TypeLayout) -> u32 {
+    match ty {
+        TypeLayout::Struct(layout) => round_up(layout.size(), layout.align()),
+        TypeLayout::Array { length, stride, .. } => stride * length.unwrap_or(0),
+        ty => ty.size(),
+    }
+}
+
+fn round_up(value: u32, align: u32) -> u32 {
+    value.div_ceil(align) * align
+}
+
+fn scalar_tokens(scalar: ScalarType) -> Result<TokenStream2, String> {
+    let tokens = match scalar {
+        ScalarType::Bool => quote!(::vkez_core::ash::vk::Bool32),
+        ScalarType::Int { width, signed } => {
+            if !matches!(width, 8 | 16 | 32 | 64) {
+                return Err(format!("Unsupported integer width {width}"));
+            }
+            let prefix = if signed { 'i' } else { 'u' };
+            let ident = format_ident!("{}{}", prefix, width);
+            quote!(#ident)
+        }
+        ScalarType::Float { width: 32 } => quote!(f32),
+        ScalarType::Float { width: 64 } => quote!(f64),
+        // No stable `f16`, keep the bits
+        ScalarType::Float { width: 16 } => quote!(u16),
+        ScalarType::Float { width } => return Err(format!("Unsupported float width {width}")),
+    };
+
+    Ok(tokens)
+}
+
+fn field_ident(name: &str, index: usize) -> Ident {
+    if let Ok(ident) = syn::parse_str::<Ident>(name) {
+        return ident;
+    }
+
+    // Keywords like `type` are valid GLSL identifiers
+    let is_identifier = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.starts_with(|c: char| c.is_ascii_alphabetic());
+    if is_identifier && !matches!(name, "self" | "Self" | "super" | "crate") {
+        return Ident::new_raw(name, Span::call_site());
+    }
+
+    format_ident!("member{}", index)
+}
+
+fn padding_field(paddings: &mut usize, size: u32) -> TokenStream2 {
+    let ident = format_ident!("_pad{}", paddings);
+    *paddings += 1;
+    let size = size as usize;
+    quote!(pub #ident: [u8; #size])
+}
+
+/// `PushConstants` struct of a shader, with its `RANGE`.
+pub(crate) fn gen_push_constants(
+    types: &mut LayoutTypes, block: &PushConstantBlock,
+) -> Result<Ident, String> {
+    let ident = types.gen_struct(&block.layout, Some(format_ident!("PushConstants")))?;
+
+    let (offset, size) = block.range();
+    let stages = stage_flags_tokens(block.stages);
+    types.items.push(quote! {
+        impl #ident {
+            pub const RANGE: ::vkez_core::ash::vk::PushConstantRange =
+                ::vkez_core::ash::vk::PushConstantRange {
+                    stage_flags: #stages,
+                    offset: #offset,
+                    size: #size,
+                };
+        }
+    });
+
+    Ok(ident)
+}
+
+/// `Specialization` struct of a shader, one field per specialization constant
+/// with the shader's value as default.
+pub(crate) fn gen_specialization(
+    types: &mut LayoutTypes, constants: &[SpecializationConstant], local_size: Option<&LocalSize>,
+) -> Result<Ident, String> {
+    let ident = format_ident!("Specialization");
+    let mut constant_fields = HashMap::new();
+
+    let mut fields = Vec::new();
+    let mut defaults = Vec::new();
+    let mut map_entries = Vec::new();
+    let mut asserts = Vec::new();
+    let mut paddings = 0;
+    let mut end = 0;
+    for (index, constant) in constants.iter().enumerate() {
+        let size = constant.size();
+        let offset = round_up(end, size);
+        if offset > end {
+            let padding = (offset - end) as usize;
+            let padding_ident = format_ident!("_pad{}", paddings);
+            fields.push(padding_field(&mut paddings, offset - end));
+            defaults.push(quote!(#padding_ident: [0; #padding]));
+        }
+        end = offset + size;
+
+        let field = field_ident(&constant.name, index);
+        let ty = scalar_tokens(constant.ty)?;
+        let default = scalar_value_tokens(constant.ty, &constant.default)
+            .ok_or_else(|| format!("Invalid default value for `{}`", constant.name))?;
+        let constant_id = constant.constant_id;
+        let offset = offset as usize;
+        let size = size as usize;
+
+        fields.push(quote!(pub #field: #ty));
+        defaults.push(quote!(#field: #default));
+        constant_fields.insert(constant_id, field.clone());
+        map_entries.push(quote! {
+            ::vkez_core::ash::vk::SpecializationMapEntry {
+                constant_id: #constant_id,
+                offset: ::core::mem::offset_of!(#ident, #field) as u32,
+                size: #size,
+            }
+        });
+        asserts.push(quote! {
+            ::core::assert!(::core::mem::offset_of!(#ident, #field) == #offset);
+        });
+    }
+
+    let align = constants.iter().map(|c| c.size()).max().unwrap_or(1);
+    let size = round_up(end, align);
+    if size > end {
+        let padding = (size - end) as usize;
+        let padding_ident = format_ident!("_pad{}", paddings);
+        fields.push(padding_field(&mut paddings, size - end));
+        defaults.push(quote!(#padding_ident: [0; #padding]));
+    }
+    let size = size as usize;
+
+    let local_size_impl = local_size
+        .filter(|local_size| local_size.iter().any(|c| c.constant_id.is_some()))
+        .map(|local_size| {
+            let components = local_size.iter().map(|component| {
+                match component
+                    .constant_id
+                    .and_then(|id| constant_fields.get(&id))
+                {
+                    Some(field) => quote!(self.#field as u32),
+                    None => {
+                        let value = component.value;
+                        quote!(#value)
+                    }
+                }
+            });
+
+            quote! {
+                impl #ident {
+                    /// Workgroup size with these constants.
+                    #[inline]
+                    pub fn local_size(&self) -> [u32; 3] {
+                        [#(#components),*]
+                    }
+                }
+            }
+        });
+
+    types.items.push(quote! {
+        #[repr(C)]
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        #[allow(non_snake_case)]
+        pub struct #ident {
+            #(#fields),*
+        }
+
+        impl ::core::default::Default for #ident {
+            #[inline]
+            fn default() -> Self {
+                Self {
+                    #(#defaults),*
+                }
+            }
+        }
+
+        #local_size_impl
+
+        impl #ident {
+            pub const MAP_ENTRIES: &'static [::vkez_core::ash::vk::SpecializationMapEntry] = &[
+                #(#map_entries),*
+            ];
+
+            /// Points to `self`, which must outlive the returned struct.
+            #[inline]
+            pub fn specialization_info(&self) -> ::vkez_core::ash::vk::SpecializationInfo {
+                ::vkez_core::ash::vk::SpecializationInfo {
+                    map_entry_count: Self::MAP_ENTRIES.len() as u32,
+                    p_map_entries: Self::MAP_ENTRIES.as_ptr(),
+                    data_size: ::core::mem::size_of::<Self>(),
+                    p_data: self as *const Self as *const ::core::ffi::c_void,
+                }
+            }
+        }
+
+        const _: () = {
+            ::core::assert!(::core::mem::size_of::<#ident>() == #size);
+            #(#asserts)*
+        };
+    });
+
+    Ok(ident)
+}
+
+/// Constant of a scalar type from its literal words.
+fn scalar_value_tokens(scalar: ScalarType, words: &[u32]) -> Option<TokenStream2> {
+    let low = *words.first()?;
+    let value = |width: u32| match width {
+        64 => Some(((*words.get(1)? as u64) << 32) | low as u64),
+        _ => Some(low as u64),
+    };
+
+    let tokens = match scalar {
+        ScalarType::Bool if low != 0 => quote!(::vkez_core::ash::vk::TRUE),
+        ScalarType::Bool => quote!(::vkez_core::ash::vk::FALSE),
+        ScalarType::Int { width, signed } => {
+            let value = value(width)?;
+            let literal = match (width, signed) {
+                (32, false) => Literal::u32_suffixed(value as u32),
+                (32, true) => Literal::i32_suffixed(value as u32 as i32),
+                (64, false) => Literal::u64_suffixed(value),
+                (64, true) => Literal::i64_suffixed(value as i64),
+                _ => return None,
+            };
+            quote!(#literal)
+        }
+        ScalarType::Float { width: 32 } => {
+            let bits = low;
+            quote!(f32::from_bits(#bits))
+        }
+        ScalarType::Float { width: 64 } => {
+            let bits = value(64)?;
+            quote!(f64::from_bits(#bits))
+        }
+        ScalarType::Float { .. } => return None,
+    };
+
+    Some(tokens)
+}
+
+/// `[x, y, z]` workgroup size with the default specialization.
+fn local_size_tokens(local_size: &LocalSize) -> TokenStream2 {
+    let components = local_size.iter().map(|component| component.value);
+    quote!([#(#components),*])
+}
+
+/// Everything reflected from a shader, `#[shader_module]` exposes it as
+/// constants and `#[shader_set]` as trait impls.
+pub(crate) struct ReflectedTypes {
+    /// Structs of the push constants, specialization constants and buffer
+    /// blocks.
+    pub items: Vec<TokenStream2>,
+    pub push_constants: Option<Ident>,
+    pub specialization: Option<Ident>,
+    /// `&[VertexAttribute]` of a vertex shader.
+    pub vertex_inputs: Option<TokenStream2>,
+    /// `[u32; 3]` of a compute, task or mesh shader.
+    pub local_size: Option<TokenStream2>,
+}
+
+pub(crate) fn gen_reflected_types(reflection: &ShaderReflection) -> Result<ReflectedTypes, String> {
+    let mut types = LayoutTypes::default();
+
+    let push_constants = reflection
+        .push_constants
+        .as_ref()
+        .map(|block| gen_push_constants(&mut types, block))
+        .transpose()?;
+    let specialization = (!reflection.specialization_constants.is_empty())
+        .then(|| {
+            gen_specialization(
+                &mut types,
+                &reflection.specialization_constants,
+                reflection.local_size.as_ref(),
+            )
+        })
+        .transpose()?;
+    for block in &reflection.buffer_blocks {
+        types.gen_block(&block.layout)?;
+    }
+
+    let vertex_inputs = (!reflection.vertex_inputs.is_empty())
+        .then(|| vertex_inputs_tokens(&reflection.vertex_inputs));
+    let local_size = reflection.local_size.as_ref().map(local_size_tokens);
+
+    Ok(ReflectedTypes {
+        items: types.items,
+        push_constants,
+        specialization,
+        vertex_inputs,
+        local_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflect::{tests::reflect_comp, ShaderReflection};
+
+    /// Generated items, without whitespace.
+    fn gen_blocks() -> String {
+        let module = reflect_comp();
+        let reflection = ShaderReflection::reflect(&module, &module.entry_points[0]).unwrap();
+
+        let mut types = LayoutTypes::default();
+        for block in &reflection.buffer_blocks {
+            types.gen_block(&block.layout).unwrap();
+        }
+
+        let items = &types.items;
+        quote!(#(#items)*)
+            .to_string()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect()
+    }
+
+    #[test]
+    fn std140_block_fields() {
+        let items = gen_blocks();
+
+        for expected in [
+            "pubdirection:[f32;3usize]",
+            "pubtime:f32",
+            "pubtransform:[[f32;4usize];4usize]",
+            // Elements padded up to the array stride
+            "puboffsets:[::vkez_core::layout::Padded<[f32;2usize],8usize>;3usize]",
+            "publight:Light",
+            "offset_of!(Params,transform)==16usize",
+            "offset_of!(Params,offsets)==80usize",
+            "offset_of!(Params,light)==128usize",
+            "size_of::<Params>()==144usize",
+            "size_of::<Light>()==16usize",
+        ] {
+            assert!(items.contains(expected), "`{expected}` not in `{items}`");
+        }
+    }
+
+    #[test]
+    fn std430_block_fields() {
+        let items = gen_blocks();
+
+        for expected in [
+            "pubpositions:[::vkez_core::layout::Padded<[f32;3usize],4usize>;4usize]",
+            "size_of::<Data>()==64usize",
+            // The runtime array is left out of the struct
+            "RuntimeArrayBlockforData{typeElement=f32;}",
+        ] {
+            assert!(items.contains(expected), "`{expected}` not in `{items}`");
+        }
+    }
+
+    #[test]
+    fn std140_rust_sizes() {
+        let module = reflect_comp();
+        let reflection = ShaderReflection::reflect(&module, &module.entry_points[0]).unwrap();
+        let params = &reflection.buffer_blocks[0].layout;
+
+        let sizes = params
+            .members
+            .iter()
+            .map(|member| (member.name.as_str(), member.offset, rust_size(&member.ty)))
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, [
+            ("direction", 0, 12),
+            ("time", 12, 4),
+            ("transform", 16, 64),
+            ("offsets", 80, 48),
+            ("light", 128, 16),
+        ]);
+    }
+}
@@ -1,33 +1,75 @@
-use std::path::PathBuf;
+use std::collections::HashSet;
 
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use proc_macro_error::{abort_if_dirty, emit_error, emit_warning, proc_macro_error};
 use quote::quote;
-use shaderc::{CompileOptions, EnvVersion, ShaderKind};
-use structmeta::StructMeta;
-use syn::{parse_macro_input, parse_quote, visit_mut::visit_item_mod_mut, ItemMod, LitStr};
+use shaderc::ShaderKind;
+use structmeta::{NameArgs, StructMeta};
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    visit_mut::visit_item_mod_mut,
+    DeriveInput, Ident, ItemMod, LitBool, LitStr, Token,
+};
 
-use crate::shader_set::AccumulateShaderItemsVisitor;
+use crate::{
+    compiler::{
+        compile_shader, env_tracking_tokens, parse_shader_kind, CompiledShader, HlslRegisterShift,
+        RelativeTo, ShaderCompileArgs, ShaderCompileOptions, ShaderDefine,
+    },
+    descriptor_set::derive_descriptor_set_impl,
+    layout::gen_reflected_types,
+    shader_set::{shader_set_impl, AccumulateShaderItemsVisitor, ShaderSetArgs},
+    tokens::{cstr_tokens, pascal_case_ident, snake_case_ident, stage_flags_tokens},
+    vertex::derive_vertex_impl,
+};
 
+mod compiler;
+mod descriptor_set;
+mod layout;
+mod reflect;
+mod sampler;
 mod shader_set;
+mod tokens;
+mod vertex;
 
 #[proc_macro_error]
 #[proc_macro_attribute]
-pub fn shader_set(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn shader_set(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as ShaderSetArgs);
     let mut item = parse_macro_input!(item as ItemMod);
     let mut raw_attributes = AccumulateShaderItemsVisitor::default();
     visit_item_mod_mut(&mut raw_attributes, &mut item);
 
-    for (shader, attributes) in &raw_attributes.shaders {
-        dbg!(shader, attributes);
+    match shader_set_impl(args, raw_attributes, item) {
+        Ok(tree) => tree.into(),
+        Err(tree) => tree.into(),
     }
+}
 
-    for (set, attributes) in &raw_attributes.descriptor_sets {
-        dbg!(set, attributes);
+#[proc_macro_error]
+#[proc_macro_derive(DescriptorSet, attributes(binding))]
+pub fn derive_descriptor_set(item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as DeriveInput);
+
+    match derive_descriptor_set_impl(item) {
+        Ok(tree) => tree.into(),
+        Err(tree) => tree.into(),
     }
+}
+
+#[proc_macro_error]
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as DeriveInput);
 
-    todo!()
+    match derive_vertex_impl(item) {
+        Ok(tree) => tree.into(),
+        Err(tree) => tree.into(),
+    }
 }
 
 type ShaderModuleItem = ItemMod;
@@ -36,8 +78,39 @@ type ShaderModuleItem = ItemMod;
 struct ShaderModuleArgs {
     #[struct_meta(unnamed)]
     path: LitStr,
+    relative_to: Option<LitStr>,
+    lang: Option<LitStr>,
     kind: Option<LitStr>,
     entry: Option<LitStr>,
+    include_dirs: Option<Vec<LitStr>>,
+    defines: Option<Vec<ShaderDefine>>,
+    permutations: Option<Vec<ShaderPermutation>>,
+    target: Option<LitStr>,
+    spirv: Option<LitStr>,
+    optimize: Option<LitStr>,
+    debug_info: Option<LitBool>,
+    auto_bind: Option<LitBool>,
+    register_shift: Option<NameArgs<HlslRegisterShift>>,
+}
+
+/// `name(DEFINE = "value", ...)`, compiled in its own `name` module.
+struct ShaderPermutation {
+    name: Ident,
+    defines: Vec<ShaderDefine>,
+}
+
+impl Parse for ShaderPermutation {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        let content;
+        parenthesized!(content in input);
+        let defines = Punctuated::<ShaderDefine, Token![,]>::parse_terminated(&content)?;
+
+        Ok(Self {
+            name,
+            defines: defines.into_iter().collect(),
+        })
+    }
 }
 
 #[proc_macro_error]
@@ -63,93 +136,151 @@ pub fn shader_module(attr: TokenStream, item: TokenStream) -> TokenStream {
 fn shader_module_impl(
     args: ShaderModuleArgs, item: ShaderModuleItem,
 ) -> Result<TokenStream2, TokenStream2> {
-    let path = PathBuf::from(args.path.value());
-    let absolute_path = if path.is_absolute() {
-        path.clone()
-    } else {
-        std::env::current_dir().unwrap().join(&path)
-    };
-
-    let shader_source = std::fs::read_to_string(&path);
-    if let Err(e) = &shader_source {
-        if path.is_relative() {
-            emit_error!(
-                &args.path,
-                format!("{e}");
-                help = "Paths are relative to the crate's root"
-            );
-        }
-    }
-    let shader_source = shader_source.unwrap();
-
-    let shader_kind = parse_shader_kind(args.kind.as_ref());
+    let shader_kind = args.kind.as_ref().map(|kind| {
+        parse_shader_kind(&kind.value()).unwrap_or_else(|| {
+            emit_warning!(kind, "Unknown shader kind, defaulting to InferFromSource"; help = "See shaderc::ShaderKind");
+            ShaderKind::InferFromSource
+        })
+    });
 
-    abort_if_dirty();
+    let relative_to = RelativeTo::parse(args.relative_to.as_ref());
+    let include_dirs = args.include_dirs.as_deref().unwrap_or_default();
+    let defines = args.defines.as_deref().unwrap_or_default();
+    let options = ShaderCompileOptions {
+        lang: args.lang.clone(),
+        target: args.target.clone(),
+        spirv: args.spirv.clone(),
+        optimize: args.optimize.clone(),
+        debug_info: args.debug_info.clone(),
+        auto_bind: args.auto_bind.clone(),
+        register_shift: args.register_shift.clone(),
+    };
 
-    let compiler = shaderc::Compiler::new().unwrap();
-
-    let entry_point = args
-        .entry
-        .as_ref()
-        .map(|e| e.value())
-        .unwrap_or("main".to_string());
-
-    let mut compile_options = CompileOptions::new().unwrap();
-    compile_options.set_target_env(shaderc::TargetEnv::Vulkan, EnvVersion::Vulkan1_1 as _);
-    compile_options.set_optimization_level(shaderc::OptimizationLevel::Performance);
-
-    let artifact = compiler
-        .compile_into_spirv(
-            &shader_source,
-            shader_kind,
-            &path.file_name().unwrap().to_string_lossy(),
-            &entry_point,
-            Some(&compile_options),
-        )
-        .map_err(|e| {
-            syn::Error::new(args.path.span(), format!("Failed to compile shader: {e}"))
-                .to_compile_error()
+    let Some(permutations) = args.permutations.as_ref() else {
+        let shaders = compile_shader(ShaderCompileArgs {
+            path: &args.path,
+            relative_to,
+            kind: shader_kind,
+            entry: args.entry.as_ref(),
+            include_dirs,
+            defines,
+            options: &options,
         })?;
 
-    emit_warning!(&args.path, artifact.get_warning_messages());
+        let content = gen_entry_points(&args.path, &shaders)?;
+        return Ok(gen_shader_module(&item, content));
+    };
 
+    let mut names = HashSet::new();
+    for permutation in permutations {
+        if !names.insert(&permutation.name) {
+            emit_error!(permutation.name, "Duplicate permutation name");
+        }
+    }
+    abort_if_dirty();
 
-    proc_macro_error::abort_if_dirty();
+    let mut variants = Vec::with_capacity(permutations.len());
+    for permutation in permutations {
+        // Permutation defines override the common ones
+        let defines = defines
+            .iter()
+            .filter(|d| !permutation.defines.iter().any(|p| p.name == d.name))
+            .chain(&permutation.defines)
+            .cloned()
+            .collect::<Vec<_>>();
 
-    let generated_module = gen_shader_module(
-        &item,
-        &absolute_path.to_string_lossy(),
-        artifact.as_binary(),
-    );
-    Ok(quote!(#generated_module))
-}
+        let shaders = compile_shader(ShaderCompileArgs {
+            path: &args.path,
+            relative_to,
+            kind: shader_kind,
+            entry: args.entry.as_ref(),
+            include_dirs,
+            defines: &defines,
+            options: &options,
+        })?;
 
-fn parse_shader_kind(kind: Option<&LitStr>) -> ShaderKind {
-    if let Some(kind) = kind {
-        match kind.value().as_str() {
-            "Compute" => ShaderKind::Compute,
-            _ => {
-                emit_warning!(kind, "Unknown shader kind, defaulting to InferFromSource"; help = "See shaderc::ShaderKind");
-                ShaderKind::InferFromSource
+        let name = &permutation.name;
+        let content = gen_entry_points(&args.path, &shaders)?;
+        variants.push(quote! {
+            pub mod #name {
+                #content
             }
-        }
-    } else {
-        ShaderKind::InferFromSource
+        });
     }
+
+    Ok(gen_shader_module(&item, quote!(#(#variants)*)))
 }
 
-fn gen_shader_module(original: &ItemMod, path: &str, code: &[u32]) -> ItemMod {
+fn gen_shader_module(original: &ItemMod, content: TokenStream2) -> TokenStream2 {
     let attrs = &original.attrs;
     let vis = &original.vis;
     let ident = &original.ident;
 
-    let code_len = code.len();
-
-    parse_quote! {
+    quote! {
         #(#attrs)*
         #vis mod #ident {
-            const _: &'static str = include_str!(#path);
-            pub const CODE: [u32; #code_len] = [#(#code),*];
+            #content
         }
     }
 }
+
+/// The content of a single entry point, or a module per entry point named
+/// after it, like `vs_main` for `VSMain`.
+fn gen_entry_points(
+    path: &LitStr, shaders: &[CompiledShader],
+) -> Result<TokenStream2, TokenStream2> {
+    if let [shader] = shaders {
+        return gen_shader_module_content(path, shader);
+    }
+
+    let mut modules = Vec::with_capacity(shaders.len());
+    for shader in shaders {
+        let name = pascal_case_ident(&shader.entry_point.name, path.span())
+            .map_err(|e| syn::Error::new(path.span(), e).to_compile_error())?;
+        let name = snake_case_ident(&name);
+        let content = gen_shader_module_content(path, shader)?;
+        modules.push(quote! {
+            pub mod #name {
+                #content
+            }
+        });
+    }
+
+    Ok(quote!(#(#modules)*))
+}
+
+fn gen_shader_module_content(
+    path: &LitStr, shader: &CompiledShader,
+) -> Result<TokenStream2, TokenStream2> {
+    let stage = shader.entry_point.stage;
+    let types = gen_reflected_types(&shader.reflection)
+        .map_err(|e| syn::Error::new(path.span(), e).to_compile_error())?;
+    let vertex_inputs = types.vertex_inputs.map(|inputs| {
+        quote!(pub const VERTEX_INPUTS: &[::vkez_core::vertex::VertexAttribute] = #inputs;)
+    });
+    let local_size = types
+        .local_size
+        .map(|local_size| quote!(pub const LOCAL_SIZE: [u32; 3] = #local_size;));
+    let types = types.items;
+    let entry_point = cstr_tokens(&shader.entry_point.name, path.span());
+
+    let path = shader.absolute_path.to_string_lossy();
+    let includes = shader.includes.iter().map(|p| p.to_string_lossy());
+    let code = shader.code_tokens();
+    let content_hash = &shader.content_hash;
+    let env_tracking = env_tracking_tokens();
+    let stage = stage_flags_tokens(stage);
+
+    Ok(quote! {
+        const _: &'static [u8] = include_bytes!(#path);
+        #(const _: &'static str = include_str!(#includes);)*
+        #env_tracking
+        pub const CODE: &[u32] = #code;
+        pub const VIBE_CHECK: &str = #content_hash;
+        pub const STAGE: ::vkez_core::ash::vk::ShaderStageFlags = #stage;
+        pub const ENTRY_POINT: &::std::ffi::CStr = #entry_point;
+        #vertex_inputs
+        #local_size
+        #(#types)*
+    })
+}
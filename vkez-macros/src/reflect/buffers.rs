@@ -0,0 +1,59 @@
+use super::{decoration, storage_class, EntryPoint, SpirvModule, StructLayout, Type};
+
+/// Uniform or storage buffer block.
+#[derive(Debug, Clone)]
+pub(crate) struct BufferBlock {
+    pub set: u32,
+    pub binding: u32,
+    pub layout: StructLayout,
+}
+
+pub(crate) fn reflect_buffer_blocks(
+    module: &SpirvModule, entry_point: &EntryPoint,
+) -> Result<Vec<BufferBlock>, String> {
+    let mut blocks = Vec::<BufferBlock>::new();
+
+    for variable in &module.variables {
+        if !matches!(
+            variable.storage_class,
+            storage_class::UNIFORM | storage_class::STORAGE_BUFFER
+        ) || !module.uses_variable(entry_point, variable.id)
+        {
+            continue;
+        }
+
+        let (Some(set), Some(binding)) = (
+            module.decoration(variable.id, decoration::DESCRIPTOR_SET),
+            module.decoration(variable.id, decoration::BINDING),
+        ) else {
+            continue;
+        };
+
+        let Some(mut ty) = module.pointee(variable.ty) else {
+            continue;
+        };
+
+        // Arrays of blocks share the block type
+        while let Some(Type::Array { element, .. } | Type::RuntimeArray { element }) =
+            module.types.get(&ty)
+        {
+            ty = *element;
+        }
+
+        if blocks
+            .iter()
+            .any(|block| block.set == set && block.binding == binding)
+        {
+            continue;
+        }
+
+        blocks.push(BufferBlock {
+            set,
+            binding,
+            layout: module.struct_layout(ty)?,
+        });
+    }
+
+    blocks.sort_by_key(|block| (block.set, block.binding));
+    Ok(blocks)
+}
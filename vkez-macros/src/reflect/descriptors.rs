@@ -0,0 +1,120 @@
+use vkez_core::ash::vk;
+
+use super::{decoration, dim, execution_model_stage, storage_class, EntryPoint, SpirvModule, Type};
+
+#[derive(Debug, Clone)]
+pub(crate) struct DescriptorBinding {
+    /// Name of the variable, or of the block when the variable is anonymous.
+    pub name: String,
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// 0 for runtime-sized arrays until a maximum count is given.
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+    /// Empty from reflection, given by the declaration of the set.
+    pub flags: vk::DescriptorBindingFlags,
+    /// Shared by every descriptor of the binding, from the declaration of the
+    /// set too.
+    pub immutable_sampler: Option<vk::SamplerCreateInfo>,
+}
+
+pub(crate) fn reflect_descriptor_bindings(
+    module: &SpirvModule, entry_point: &EntryPoint,
+) -> Result<Vec<DescriptorBinding>, String> {
+    let stages = execution_model_stage(entry_point.execution_model);
+    let mut bindings = Vec::<DescriptorBinding>::new();
+
+    for variable in &module.variables {
+        if !matches!(
+            variable.storage_class,
+            storage_class::UNIFORM_CONSTANT
+                | storage_class::UNIFORM
+                | storage_class::STORAGE_BUFFER
+        ) || !module.uses_variable(entry_point, variable.id)
+        {
+            continue;
+        }
+
+        let (Some(set), Some(binding)) = (
+            module.decoration(variable.id, decoration::DESCRIPTOR_SET),
+            module.decoration(variable.id, decoration::BINDING),
+        ) else {
+            continue;
+        };
+
+        let Some(mut ty) = module.pointee(variable.ty) else {
+            continue;
+        };
+
+        // Unwrap arrays of descriptors
+        let mut count = 1;
+        loop {
+            match module.types.get(&ty) {
+                Some(Type::Array { element, length }) => {
+                    count *= module.constant_u32(*length).unwrap_or(1);
+                    ty = *element;
+                }
+                Some(Type::RuntimeArray { element }) => {
+                    count = 0;
+                    ty = *element;
+                }
+                _ => break,
+            }
+        }
+
+        let descriptor_type = match (variable.storage_class, module.types.get(&ty)) {
+            (storage_class::STORAGE_BUFFER, _) => vk::DescriptorType::STORAGE_BUFFER,
+            (storage_class::UNIFORM, _) if module.has_decoration(ty, decoration::BUFFER_BLOCK) => {
+                vk::DescriptorType::STORAGE_BUFFER
+            }
+            (storage_class::UNIFORM, _) => vk::DescriptorType::UNIFORM_BUFFER,
+            (_, Some(Type::Sampler)) => vk::DescriptorType::SAMPLER,
+            (_, Some(Type::SampledImage)) => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            (_, Some(Type::AccelerationStructure)) => {
+                vk::DescriptorType::ACCELERATION_STRUCTURE_KHR
+            }
+            (_, Some(Type::Image { dim, sampled })) => match (*dim, *sampled) {
+                (dim::SUBPASS_DATA, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+                (dim::BUFFER, 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+                (dim::BUFFER, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+                (_, 2) => vk::DescriptorType::STORAGE_IMAGE,
+                _ => vk::DescriptorType::SAMPLED_IMAGE,
+            },
+            _ => continue,
+        };
+
+        let name = [variable.id, ty]
+            .iter()
+            .filter_map(|id| module.names.get(id))
+            .find(|name| !name.is_empty())
+            .cloned()
+            .unwrap_or_default();
+
+        if let Some(existing) = bindings
+            .iter_mut()
+            .find(|b| b.set == set && b.binding == binding)
+        {
+            if existing.descriptor_type != descriptor_type || existing.count != count {
+                return Err(format!(
+                    "Conflicting declarations for set = {set}, binding = {binding}"
+                ));
+            }
+            continue;
+        }
+
+        bindings.push(DescriptorBinding {
+            name,
+            set,
+            binding,
+            descriptor_type,
+            count,
+            stages,
+            flags: vk::DescriptorBindingFlags::empty(),
+            immutable_sampler: None,
+        });
+    }
+
+    bindings.sort_by_key(|b| (b.set, b.binding));
+    Ok(bindings)
+}
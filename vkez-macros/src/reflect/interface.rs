@@ -0,0 +1,291 @@
+//! User-defined `in` and `out` variables of an entry point, split into one
+//! entry per location so that stages can be matched against each other.
+
+use super::{
+    decoration, execution_model, storage_class, EntryPoint, ScalarType, SpirvModule, Type,
+};
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StageInterface {
+    pub inputs: Vec<InterfaceVariable>,
+    pub outputs: Vec<InterfaceVariable>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct InterfaceVariable {
+    pub name: String,
+    pub location: u32,
+    pub component: u32,
+    pub ty: InterfaceType,
+    pub interpolation: Interpolation,
+    /// Per-patch rather than per-vertex, for tessellation stages.
+    pub patch: bool,
+    /// Per-primitive rather than per-vertex, for mesh and fragment stages.
+    pub per_primitive: bool,
+}
+
+/// Scalar or vector held by a single location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct InterfaceType {
+    pub scalar: ScalarType,
+    pub components: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Interpolation {
+    pub flat: bool,
+    pub no_perspective: bool,
+    pub centroid: bool,
+    pub sample: bool,
+}
+
+impl InterfaceType {
+    /// Integers and doubles are never interpolated, fragment shaders must
+    /// read them `flat`.
+    pub fn needs_flat(self) -> bool {
+        matches!(
+            self.scalar,
+            ScalarType::Int { .. } | ScalarType::Float { width: 64 }
+        )
+    }
+
+    /// GLSL name of the type, like `vec3` or `uint`.
+    pub fn describe(self) -> String {
+        let (scalar, prefix) = match self.scalar {
+            ScalarType::Bool => ("bool", "b"),
+            ScalarType::Int { signed: true, .. } => ("int", "i"),
+            ScalarType::Int { signed: false, .. } => ("uint", "u"),
+            ScalarType::Float { width: 64 } => ("double", "d"),
+            ScalarType::Float { .. } => ("float", ""),
+        };
+
+        match self.components {
+            1 => scalar.to_string(),
+            components => format!("{prefix}vec{components}"),
+        }
+    }
+}
+
+impl Interpolation {
+    /// `centroid` or `sample`, which only make sense when both stages agree
+    /// on them.
+    pub fn auxiliary(self) -> Option<&'static str> {
+        match (self.centroid, self.sample) {
+            (_, true) => Some("sample"),
+            (true, false) => Some("centroid"),
+            (false, false) => None,
+        }
+    }
+
+    pub fn describe(self) -> String {
+        let qualifiers = [
+            (self.flat, "flat"),
+            (self.no_perspective, "noperspective"),
+            (self.centroid, "centroid"),
+            (self.sample, "sample"),
+        ]
+        .into_iter()
+        .filter(|(present, _)| *present)
+        .map(|(_, qualifier)| qualifier)
+        .collect::<Vec<_>>();
+
+        if qualifiers.is_empty() {
+            "smooth".to_string()
+        } else {
+            qualifiers.join(" ")
+        }
+    }
+}
+
+pub(crate) fn reflect_stage_interface(
+    module: &SpirvModule, entry_point: &EntryPoint,
+) -> Result<StageInterface, String> {
+    // Tessellation, geometry and mesh stages see arrays with an element per
+    // vertex, or per primitive for the outputs of mesh stages
+    let (arrayed_inputs, arrayed_outputs) = match entry_point.execution_model {
+        execution_model::TESSELLATION_CONTROL => (true, true),
+        execution_model::TESSELLATION_EVALUATION | execution_model::GEOMETRY => (true, false),
+        execution_model::MESH_NV | execution_model::MESH_EXT => (false, true),
+        _ => (false, false),
+    };
+
+    let mut interface = StageInterface::default();
+    for variable in &module.variables {
+        let (variables, arrayed) = match variable.storage_class {
+            storage_class::INPUT => (&mut interface.inputs, arrayed_inputs),
+            storage_class::OUTPUT => (&mut interface.outputs, arrayed_outputs),
+            _ => continue,
+        };
+        if !entry_point.interface.contains(&variable.id)
+            || module.has_decoration(variable.id, decoration::BUILT_IN)
+        {
+            continue;
+        }
+
+        let Some(mut ty) = module.pointee(variable.ty) else {
+            continue;
+        };
+
+        let patch = module.has_decoration(variable.id, decoration::PATCH);
+        let per_primitive = module.has_decoration(variable.id, decoration::PER_PRIMITIVE);
+        if arrayed && !patch {
+            if let Some(Type::Array { element, .. } | Type::RuntimeArray { element }) =
+                module.types.get(&ty)
+            {
+                ty = *element;
+            }
+        }
+
+        // Blocks of built-ins like `gl_PerVertex`
+        if let Some(Type::Struct { members }) = module.types.get(&ty) {
+            if (0..members.len() as u32).any(|member| {
+                module
+                    .member_decoration(ty, member, decoration::BUILT_IN)
+                    .is_some()
+            }) {
+                continue;
+            }
+        }
+
+        let name = module.names.get(&variable.id).cloned().unwrap_or_default();
+        let location = module.decoration(variable.id, decoration::LOCATION);
+        let location = match (location, module.types.get(&ty)) {
+            (Some(location), _) => location,
+            // Members of blocks may have their own locations
+            (None, Some(Type::Struct { .. })) => 0,
+            (None, _) => return Err(format!("Interface variable `{name}` has no location")),
+        };
+
+        let mut locations = Vec::new();
+        flatten(module, ty, location, &name, &mut locations)?;
+
+        let component = module
+            .decoration(variable.id, decoration::COMPONENT)
+            .unwrap_or(0);
+        let interpolation = Interpolation {
+            flat: module.has_decoration(variable.id, decoration::FLAT),
+            no_perspective: module.has_decoration(variable.id, decoration::NO_PERSPECTIVE),
+            centroid: module.has_decoration(variable.id, decoration::CENTROID),
+            sample: module.has_decoration(variable.id, decoration::SAMPLE),
+        };
+        variables.extend(
+            locations
+                .into_iter()
+                .map(|(name, location, ty)| InterfaceVariable {
+                    name,
+                    location,
+                    component,
+                    ty,
+                    interpolation,
+                    patch,
+                    per_primitive,
+                }),
+        );
+    }
+
+    interface.inputs.sort_by_key(|v| (v.location, v.component));
+    interface.outputs.sort_by_key(|v| (v.location, v.component));
+    Ok(interface)
+}
+
+/// Pushes the name and type of each location of `ty`, returns the next free
+/// location.
+fn flatten(
+    module: &SpirvModule, ty: u32, location: u32, name: &str,
+    locations: &mut Vec<(String, u32, InterfaceType)>,
+) -> Result<u32, String> {
+    let mut push = |scalar, components| {
+        locations.push((name.to_string(), location, InterfaceType {
+            scalar,
+            components,
+        }));
+        // 64-bit vectors of more than two components take two locations
+        match scalar {
+            ScalarType::Int { width: 64, .. } | ScalarType::Float { width: 64 }
+                if components > 2 =>
+            {
+                location + 2
+            }
+            _ => location + 1,
+        }
+    };
+
+    let next = match module.types.get(&ty) {
+        Some(Type::Bool | Type::Int { .. } | Type::Float { .. }) => {
+            push(module.scalar_type(ty)?, 1)
+        }
+        Some(Type::Vector { component, count }) => push(module.scalar_type(*component)?, *count),
+        Some(Type::Matrix { column, count }) => {
+            let mut next = location;
+            for _ in 0..*count {
+                next = flatten(module, *column, next, name, locations)?;
+            }
+            next
+        }
+        Some(Type::Array { element, length }) => {
+            let length = module
+                .constant_u32(*length)
+                .ok_or_else(|| format!("Unsupported array length for `{name}`"))?;
+            let mut next = location;
+            for _ in 0..length {
+                next = flatten(module, *element, next, name, locations)?;
+            }
+            next
+        }
+        Some(Type::Struct { members }) => {
+            let mut next = location;
+            for (index, member) in members.iter().enumerate() {
+                let index = index as u32;
+                if let Some(location) = module.member_decoration(ty, index, decoration::LOCATION) {
+                    next = location;
+                }
+                let member_name = match module.member_names.get(&(ty, index)) {
+                    Some(member) => format!("{name}.{member}"),
+                    None => name.to_string(),
+                };
+                next = flatten(module, *member, next, &member_name, locations)?;
+            }
+            next
+        }
+        _ => {
+            return Err(format!(
+                "Unsupported type for the interface variable `{name}`"
+            ))
+        }
+    };
+
+    Ok(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflect::module::tests::interface_mesh;
+
+    #[test]
+    fn mesh_outputs() {
+        let module = interface_mesh();
+        let interface = reflect_stage_interface(&module, &module.entry_points[0]).unwrap();
+
+        assert!(interface.inputs.is_empty());
+        // Without the arrays of an element per vertex or primitive, nor the
+        // built-ins
+        let outputs = interface
+            .outputs
+            .iter()
+            .map(|v| {
+                (
+                    v.name.as_str(),
+                    v.location,
+                    v.ty.describe(),
+                    v.per_primitive,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(outputs, [
+            ("color", 0, "vec4".to_string(), false),
+            ("material", 1, "uint".to_string(), true)
+        ]);
+        assert!(interface.outputs[1].interpolation.flat);
+    }
+}
@@ -0,0 +1,347 @@
+//! Explicit layout of the types of buffer blocks and push constants, as
+//! decorated by the compiler.
+
+use super::{decoration, SpirvModule, Type};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ScalarType {
+    /// 32 bits in memory
+    Bool,
+    Int {
+        width: u32,
+        signed: bool,
+    },
+    Float {
+        width: u32,
+    },
+}
+
+impl ScalarType {
+    pub fn size(self) -> u32 {
+        match self {
+            Self::Bool => 4,
+            Self::Int { width, .. } | Self::Float { width } => width / 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TypeLayout {
+    Scalar(ScalarType),
+    Vector {
+        scalar: ScalarType,
+        count: u32,
+    },
+    /// `vectors` columns (or rows when `row_major`) of `count` scalars,
+    /// `stride` bytes apart.
+    Matrix {
+        scalar: ScalarType,
+        vectors: u32,
+        count: u32,
+        stride: u32,
+        row_major: bool,
+    },
+    /// `length` is `None` for runtime-sized arrays.
+    Array {
+        element: Box<TypeLayout>,
+        length: Option<u32>,
+        stride: u32,
+    },
+    Struct(StructLayout),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StructLayout {
+    pub id: u32,
+    pub name: String,
+    pub members: Vec<MemberLayout>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MemberLayout {
+    pub name: String,
+    pub offset: u32,
+    pub ty: TypeLayout,
+}
+
+impl TypeLayout {
+    /// Size in bytes, runtime-sized arrays count as empty.
+    pub fn size(&self) -> u32 {
+        match self {
+            Self::Scalar(scalar) => scalar.size(),
+            Self::Vector { scalar, count } => scalar.size() * count,
+            Self::Matrix {
+                vectors, stride, ..
+            } => stride * vectors,
+            Self::Array { length, stride, .. } => stride * length.unwrap_or(0),
+            Self::Struct(layout) => layout.size(),
+        }
+    }
+
+    /// Alignment of the matching Rust type.
+    pub fn align(&self) -> u32 {
+        match self {
+            Self::Scalar(scalar) | Self::Vector { scalar, .. } | Self::Matrix { scalar, .. } => {
+                scalar.size()
+            }
+            Self::Array { element, .. } => element.align(),
+            Self::Struct(layout) => layout.align(),
+        }
+    }
+}
+
+impl StructLayout {
+    /// End of the last member, without trailing padding.
+    pub fn size(&self) -> u32 {
+        self.members
+            .iter()
+            .map(|member| member.offset + member.ty.size())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn align(&self) -> u32 {
+        self.members
+            .iter()
+            .map(|member| member.ty.align())
+            .max()
+            .unwrap_or(1)
+    }
+}
+
+/// Matrix layout, decorated on the struct member containing the matrix.
+#[derive(Clone, Copy)]
+struct MatrixLayout {
+    stride: u32,
+    row_major: bool,
+}
+
+impl SpirvModule {
+    pub fn struct_layout(&self, id: u32) -> Result<StructLayout, String> {
+        let Some(Type::Struct { members }) = self.types.get(&id) else {
+            return Err(format!("Expected a struct type for %{id}"));
+        };
+
+        let name = self
+            .names
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| format!("Struct{id}"));
+
+        let members = members
+            .iter()
+            .enumerate()
+            .map(|(index, &ty)| {
+                let index = index as u32;
+                let member_name = self
+                    .member_names
+                    .get(&(id, index))
+                    .filter(|member_name| !member_name.is_empty())
+                    .cloned()
+                    .unwrap_or_else(|| format!("member{index}"));
+
+                let Some(offset) = self.member_decoration(id, index, decoration::OFFSET) else {
+                    return Err(format!(
+                        "Member `{member_name}` of `{name}` has no explicit offset"
+                    ));
+                };
+
+                let matrix = self
+                    .member_decoration(id, index, decoration::MATRIX_STRIDE)
+                    .map(|stride| MatrixLayout {
+                        stride,
+                        row_major: self
+                            .member_decoration(id, index, decoration::ROW_MAJOR)
+                            .is_some(),
+                    });
+
+                Ok(MemberLayout {
+                    ty: self
+                        .type_layout(ty, matrix)
+                        .map_err(|e| format!("{e} (member `{member_name}` of `{name}`)"))?,
+                    name: member_name,
+                    offset,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(StructLayout { id, name, members })
+    }
+
+    fn type_layout(&self, ty: u32, matrix: Option<MatrixLayout>) -> Result<TypeLayout, String> {
+        let layout = match self.types.get(&ty) {
+            Some(Type::Bool) => TypeLayout::Scalar(ScalarType::Bool),
+            Some(Type::Int { width, signed }) => TypeLayout::Scalar(ScalarType::Int {
+                width: *width,
+                signed: *signed,
+            }),
+            Some(Type::Float { width }) => TypeLayout::Scalar(ScalarType::Float { width: *width }),
+            Some(Type::Vector { component, count }) => TypeLayout::Vector {
+                scalar: self.scalar_type(*component)?,
+                count: *count,
+            },
+            Some(Type::Matrix { column, count }) => {
+                let Some(Type::Vector {
+                    component,
+                    count: rows,
+                }) = self.types.get(column)
+                else {
+                    return Err("Invalid matrix column type".to_string());
+                };
+                let Some(matrix) = matrix else {
+                    return Err("Matrix without an explicit stride".to_string());
+                };
+
+                let (vectors, count) = if matrix.row_major {
+                    (*rows, *count)
+                } else {
+                    (*count, *rows)
+                };
+
+                TypeLayout::Matrix {
+                    scalar: self.scalar_type(*component)?,
+                    vectors,
+                    count,
+                    stride: matrix.stride,
+                    row_major: matrix.row_major,
+                }
+            }
+            Some(Type::Array { element, length }) => TypeLayout::Array {
+                element: Box::new(self.type_layout(*element, matrix)?),
+                length: Some(
+                    self.constant_u32(*length)
+                        .ok_or("Arrays sized by specialization constants are not supported")?,
+                ),
+                stride: self.array_stride(ty)?,
+            },
+            Some(Type::RuntimeArray { element }) => TypeLayout::Array {
+                element: Box::new(self.type_layout(*element, matrix)?),
+                length: None,
+                stride: self.array_stride(ty)?,
+            },
+            Some(Type::Struct { .. }) => TypeLayout::Struct(self.struct_layout(ty)?),
+            _ => return Err(format!("Unsupported type %{ty} in a block")),
+        };
+
+        Ok(layout)
+    }
+
+    pub fn scalar_type(&self, ty: u32) -> Result<ScalarType, String> {
+        match self.type_layout(ty, None)? {
+            TypeLayout::Scalar(scalar) => Ok(scalar),
+            _ => Err(format!("Expected a scalar type for %{ty}")),
+        }
+    }
+
+    fn array_stride(&self, ty: u32) -> Result<u32, String> {
+        self.decoration(ty, decoration::ARRAY_STRIDE)
+            .ok_or_else(|| "Array without an explicit stride".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reflect::module::tests::reflect_comp;
+
+    fn block(module: &SpirvModule, name: &str) -> StructLayout {
+        let (&id, _) = module
+            .names
+            .iter()
+            .find(|(_, block_name)| *block_name == name)
+            .unwrap();
+        module.struct_layout(id).unwrap()
+    }
+
+    fn member<'a>(layout: &'a StructLayout, name: &str) -> &'a MemberLayout {
+        layout
+            .members
+            .iter()
+            .find(|member| member.name == name)
+            .unwrap()
+    }
+
+    const F32: ScalarType = ScalarType::Float { width: 32 };
+
+    #[test]
+    fn std140_block() {
+        let params = block(&reflect_comp(), "Params");
+
+        let direction = member(&params, "direction");
+        assert_eq!(direction.offset, 0);
+        assert_eq!(direction.ty, TypeLayout::Vector {
+            scalar: F32,
+            count: 3
+        });
+        assert_eq!(direction.ty.size(), 12);
+
+        // Packed after the `vec3`
+        let time = member(&params, "time");
+        assert_eq!(time.offset, 12);
+        assert_eq!(time.ty, TypeLayout::Scalar(F32));
+
+        let transform = member(&params, "transform");
+        assert_eq!(transform.offset, 16);
+        assert_eq!(transform.ty, TypeLayout::Matrix {
+            scalar: F32,
+            vectors: 4,
+            count: 4,
+            stride: 16,
+            row_major: false,
+        });
+        assert_eq!(transform.ty.size(), 64);
+
+        // std140 rounds the stride of arrays up to 16 bytes
+        let offsets = member(&params, "offsets");
+        assert_eq!(offsets.offset, 80);
+        assert_eq!(offsets.ty, TypeLayout::Array {
+            element: Box::new(TypeLayout::Vector {
+                scalar: F32,
+                count: 2
+            }),
+            length: Some(3),
+            stride: 16,
+        });
+        assert_eq!(offsets.ty.size(), 48);
+
+        let light = member(&params, "light");
+        assert_eq!(light.offset, 128);
+        let TypeLayout::Struct(light) = &light.ty else {
+            panic!("Expected a struct");
+        };
+        assert_eq!(light.name, "Light");
+        assert_eq!(member(light, "intensity").offset, 12);
+        assert_eq!(light.size(), 16);
+
+        assert_eq!(params.size(), 144);
+        assert_eq!(params.align(), 4);
+    }
+
+    #[test]
+    fn std430_block() {
+        let data = block(&reflect_comp(), "Data");
+
+        // `vec3` elements are still 16 bytes apart
+        let positions = member(&data, "positions");
+        assert_eq!(positions.offset, 0);
+        assert_eq!(positions.ty, TypeLayout::Array {
+            element: Box::new(TypeLayout::Vector {
+                scalar: F32,
+                count: 3
+            }),
+            length: Some(4),
+            stride: 16,
+        });
+
+        let values = member(&data, "values");
+        assert_eq!(values.offset, 64);
+        assert_eq!(values.ty, TypeLayout::Array {
+            element: Box::new(TypeLayout::Scalar(F32)),
+            length: None,
+            stride: 4,
+        });
+
+        // The runtime array counts as empty
+        assert_eq!(data.size(), 64);
+    }
+}
@@ -0,0 +1,81 @@
+mod buffers;
+mod descriptors;
+mod interface;
+mod layout;
+mod module;
+mod push_constants;
+mod specialization;
+mod vertex;
+mod workgroup;
+
+pub(crate) use buffers::*;
+pub(crate) use descriptors::*;
+pub(crate) use interface::*;
+pub(crate) use layout::*;
+pub(crate) use module::*;
+pub(crate) use push_constants::*;
+pub(crate) use specialization::*;
+pub(crate) use vertex::*;
+use vkez_core::ash::vk;
+pub(crate) use workgroup::*;
+
+#[derive(Debug, Clone)]
+pub(crate) struct ShaderEntryPoint {
+    pub name: String,
+    pub stage: vk::ShaderStageFlags,
+    pub interface: StageInterface,
+}
+
+impl ShaderEntryPoint {
+    pub fn reflect(module: &SpirvModule, entry_point: &EntryPoint) -> Result<Self, String> {
+        Ok(Self {
+            name: entry_point.name.clone(),
+            stage: execution_model_stage(entry_point.execution_model),
+            interface: reflect_stage_interface(module, entry_point)?,
+        })
+    }
+}
+
+/// Resources of a single entry point, the other entry points of the module
+/// are not part of the pipeline stage.
+pub(crate) struct ShaderReflection {
+    pub descriptor_bindings: Vec<DescriptorBinding>,
+    pub push_constants: Option<PushConstantBlock>,
+    pub buffer_blocks: Vec<BufferBlock>,
+    pub specialization_constants: Vec<SpecializationConstant>,
+    pub local_size: Option<LocalSize>,
+    pub vertex_inputs: Vec<VertexInput>,
+}
+
+impl ShaderReflection {
+    pub fn reflect(module: &SpirvModule, entry_point: &EntryPoint) -> Result<Self, String> {
+        Ok(Self {
+            descriptor_bindings: reflect_descriptor_bindings(module, entry_point)?,
+            push_constants: reflect_push_constants(module, entry_point)?,
+            buffer_blocks: reflect_buffer_blocks(module, entry_point)?,
+            specialization_constants: reflect_specialization_constants(module)?,
+            local_size: reflect_local_size(module, entry_point)?,
+            vertex_inputs: reflect_vertex_inputs(module, entry_point)?,
+        })
+    }
+}
+
+pub(crate) fn execution_model_stage(execution_model: u32) -> vk::ShaderStageFlags {
+    match execution_model {
+        execution_model::VERTEX => vk::ShaderStageFlags::VERTEX,
+        execution_model::TESSELLATION_CONTROL => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        execution_model::TESSELLATION_EVALUATION => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        execution_model::GEOMETRY => vk::ShaderStageFlags::GEOMETRY,
+        execution_model::FRAGMENT => vk::ShaderStageFlags::FRAGMENT,
+        execution_model::GL_COMPUTE => vk::ShaderStageFlags::COMPUTE,
+        execution_model::TASK_NV | execution_model::TASK_EXT => vk::ShaderStageFlags::TASK_EXT,
+        execution_model::MESH_NV | execution_model::MESH_EXT => vk::ShaderStageFlags::MESH_EXT,
+        execution_model::RAY_GENERATION => vk::ShaderStageFlags::RAYGEN_KHR,
+        execution_model::INTERSECTION => vk::ShaderStageFlags::INTERSECTION_KHR,
+        execution_model::ANY_HIT => vk::ShaderStageFlags::ANY_HIT_KHR,
+        execution_model::CLOSEST_HIT => vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+        execution_model::MISS => vk::ShaderStageFlags::MISS_KHR,
+        execution_model::CALLABLE => vk::ShaderStageFlags::CALLABLE_KHR,
+        _ => vk::ShaderStageFlags::empty(),
+    }
+}
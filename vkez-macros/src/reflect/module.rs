@@ -0,0 +1,698 @@
+//! Minimal SPIR-V parser, only keeps what reflection needs from the module's
+//! global section, and the ids referenced by each function. Unknown
+//! instructions are skipped so that newer extensions don't break reflection.
+
+use std::collections::{HashMap, HashSet};
+
+pub(crate) const MAGIC: u32 = 0x0723_0203;
+
+pub(crate) mod op {
+    pub const NAME: u16 = 5;
+    pub const MEMBER_NAME: u16 = 6;
+    pub const ENTRY_POINT: u16 = 15;
+    pub const EXECUTION_MODE: u16 = 16;
+    pub const TYPE_BOOL: u16 = 20;
+    pub const TYPE_INT: u16 = 21;
+    pub const TYPE_FLOAT: u16 = 22;
+    pub const TYPE_VECTOR: u16 = 23;
+    pub const TYPE_MATRIX: u16 = 24;
+    pub const TYPE_IMAGE: u16 = 25;
+    pub const TYPE_SAMPLER: u16 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u16 = 27;
+    pub const TYPE_ARRAY: u16 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u16 = 29;
+    pub const TYPE_STRUCT: u16 = 30;
+    pub const TYPE_POINTER: u16 = 32;
+    pub const CONSTANT: u16 = 43;
+    pub const CONSTANT_COMPOSITE: u16 = 44;
+    pub const SPEC_CONSTANT_TRUE: u16 = 48;
+    pub const SPEC_CONSTANT_FALSE: u16 = 49;
+    pub const SPEC_CONSTANT: u16 = 50;
+    pub const SPEC_CONSTANT_COMPOSITE: u16 = 51;
+    pub const FUNCTION: u16 = 54;
+    pub const FUNCTION_END: u16 = 56;
+    pub const FUNCTION_CALL: u16 = 57;
+    pub const VARIABLE: u16 = 59;
+    pub const DECORATE: u16 = 71;
+    pub const MEMBER_DECORATE: u16 = 72;
+    pub const VECTOR_SHUFFLE: u16 = 79;
+    pub const COMPOSITE_EXTRACT: u16 = 81;
+    pub const COMPOSITE_INSERT: u16 = 82;
+    pub const EXECUTION_MODE_ID: u16 = 331;
+    pub const TYPE_ACCELERATION_STRUCTURE: u16 = 5341;
+}
+
+pub(crate) mod decoration {
+    pub const SPEC_ID: u32 = 1;
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const ROW_MAJOR: u32 = 4;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const MATRIX_STRIDE: u32 = 7;
+    pub const BUILT_IN: u32 = 11;
+    pub const NO_PERSPECTIVE: u32 = 13;
+    pub const FLAT: u32 = 14;
+    pub const PATCH: u32 = 15;
+    pub const CENTROID: u32 = 16;
+    pub const SAMPLE: u32 = 17;
+    pub const LOCATION: u32 = 30;
+    pub const COMPONENT: u32 = 31;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+    pub const PER_PRIMITIVE: u32 = 5271;
+}
+
+pub(crate) mod storage_class {
+    pub const UNIFORM_CONSTANT: u32 = 0;
+    pub const INPUT: u32 = 1;
+    pub const UNIFORM: u32 = 2;
+    pub const OUTPUT: u32 = 3;
+    pub const PUSH_CONSTANT: u32 = 9;
+    pub const STORAGE_BUFFER: u32 = 12;
+}
+
+pub(crate) mod execution_model {
+    pub const VERTEX: u32 = 0;
+    pub const TESSELLATION_CONTROL: u32 = 1;
+    pub const TESSELLATION_EVALUATION: u32 = 2;
+    pub const GEOMETRY: u32 = 3;
+    pub const FRAGMENT: u32 = 4;
+    pub const GL_COMPUTE: u32 = 5;
+    pub const TASK_NV: u32 = 5267;
+    pub const MESH_NV: u32 = 5268;
+    pub const RAY_GENERATION: u32 = 5313;
+    pub const INTERSECTION: u32 = 5314;
+    pub const ANY_HIT: u32 = 5315;
+    pub const CLOSEST_HIT: u32 = 5316;
+    pub const MISS: u32 = 5317;
+    pub const CALLABLE: u32 = 5318;
+    pub const TASK_EXT: u32 = 5364;
+    pub const MESH_EXT: u32 = 5365;
+}
+
+pub(crate) mod execution_mode {
+    pub const LOCAL_SIZE: u32 = 17;
+    pub const LOCAL_SIZE_ID: u32 = 38;
+}
+
+pub(crate) mod built_in {
+    pub const WORKGROUP_SIZE: u32 = 25;
+}
+
+pub(crate) mod dim {
+    pub const BUFFER: u32 = 5;
+    pub const SUBPASS_DATA: u32 = 6;
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Type {
+    Bool,
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+    AccelerationStructure,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Variable {
+    pub id: u32,
+    pub ty: u32,
+    pub storage_class: u32,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct SpecConstant {
+    pub id: u32,
+    pub ty: u32,
+    /// Literal words of the default value, `1` or `0` for booleans.
+    pub default: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct EntryPoint {
+    pub name: String,
+    pub execution_model: u32,
+    pub id: u32,
+    pub interface: Vec<u32>,
+}
+
+/// Body of a function, for the static call tree of the entry points.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Function {
+    pub calls: Vec<u32>,
+    /// Every id operand of the instructions. Literal operands may be taken
+    /// for ids too, which can only add variables to the entry point.
+    pub references: HashSet<u32>,
+}
+
+/// Decorations and their operands.
+pub(crate) type Decorations = Vec<(u32, Vec<u32>)>;
+
+#[derive(Default, Debug)]
+pub(crate) struct SpirvModule {
+    pub version: (u8, u8),
+    pub names: HashMap<u32, String>,
+    pub member_names: HashMap<(u32, u32), String>,
+    pub decorations: HashMap<u32, Decorations>,
+    pub member_decorations: HashMap<(u32, u32), Decorations>,
+    pub types: HashMap<u32, Type>,
+    pub constants: HashMap<u32, Vec<u32>>,
+    /// Constituents of constant and specialization constant composites.
+    pub composites: HashMap<u32, Vec<u32>>,
+    pub spec_constants: Vec<SpecConstant>,
+    pub variables: Vec<Variable>,
+    pub entry_points: Vec<EntryPoint>,
+    /// Entry point, mode and operands of `OpExecutionMode` and
+    /// `OpExecutionModeId`.
+    pub execution_modes: Vec<(u32, u32, Vec<u32>)>,
+    pub functions: HashMap<u32, Function>,
+}
+
+fn parse_string(words: &[u32]) -> (String, usize) {
+    let mut bytes = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        for byte in word.to_le_bytes() {
+            if byte == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), i + 1);
+            }
+            bytes.push(byte);
+        }
+    }
+
+    (String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+
+impl SpirvModule {
+    pub fn parse(code: &[u32]) -> Result<Self, String> {
+        if code.len() < 5 {
+            return Err("SPIR-V module is too small".to_string());
+        }
+        if code[0] != MAGIC {
+            return Err(format!("Invalid SPIR-V magic number {:#010x}", code[0]));
+        }
+
+        let mut module = SpirvModule {
+            version: ((code[1] >> 16) as u8, (code[1] >> 8) as u8),
+            ..Default::default()
+        };
+
+        let mut function = None;
+        let mut words = &code[5..];
+        while !words.is_empty() {
+            let word_count = (words[0] >> 16) as usize;
+            let opcode = words[0] as u16;
+            if word_count == 0 || word_count > words.len() {
+                return Err("Malformed SPIR-V instruction".to_string());
+            }
+
+            let operands = &words[1..word_count];
+            words = &words[word_count..];
+
+            match (opcode, function) {
+                (op::FUNCTION, _) if operands.len() >= 2 => {
+                    function = Some(operands[1]);
+                    module.functions.insert(operands[1], Function::default());
+                }
+                (op::FUNCTION_END, _) => function = None,
+                (_, Some(function)) => {
+                    let function = module.functions.get_mut(&function).unwrap();
+                    function.parse_instruction(opcode, operands);
+                }
+                (_, None) => module.parse_instruction(opcode, operands),
+            }
+        }
+
+        Ok(module)
+    }
+
+    fn parse_instruction(&mut self, opcode: u16, operands: &[u32]) {
+        // Truncated instructions are ignored, they will be caught by the
+        // validation layers anyway
+        let min = |n: usize| operands.len() >= n;
+
+        match opcode {
+            op::ENTRY_POINT if min(3) => {
+                let (name, len) = parse_string(&operands[2..]);
+                self.entry_points.push(EntryPoint {
+                    name,
+                    execution_model: operands[0],
+                    id: operands[1],
+                    interface: operands[2 + len..].to_vec(),
+                });
+            }
+            op::EXECUTION_MODE | op::EXECUTION_MODE_ID if min(2) => {
+                self.execution_modes
+                    .push((operands[0], operands[1], operands[2..].to_vec()));
+            }
+            op::NAME if min(2) => {
+                self.names
+                    .insert(operands[0], parse_string(&operands[1..]).0);
+            }
+            op::MEMBER_NAME if min(3) => {
+                self.member_names
+                    .insert((operands[0], operands[1]), parse_string(&operands[2..]).0);
+            }
+            op::MEMBER_DECORATE if min(3) => {
+                self.member_decorations
+                    .entry((operands[0], operands[1]))
+                    .or_default()
+                    .push((operands[2], operands[3..].to_vec()));
+            }
+            op::DECORATE if min(2) => {
+                self.decorations
+                    .entry(operands[0])
+                    .or_default()
+                    .push((operands[1], operands[2..].to_vec()));
+            }
+            op::TYPE_BOOL if min(1) => {
+                self.types.insert(operands[0], Type::Bool);
+            }
+            op::TYPE_INT if min(3) => {
+                self.types.insert(operands[0], Type::Int {
+                    width: operands[1],
+                    signed: operands[2] != 0,
+                });
+            }
+            op::TYPE_FLOAT if min(2) => {
+                self.types
+                    .insert(operands[0], Type::Float { width: operands[1] });
+            }
+            op::TYPE_VECTOR if min(3) => {
+                self.types.insert(operands[0], Type::Vector {
+                    component: operands[1],
+                    count: operands[2],
+                });
+            }
+            op::TYPE_MATRIX if min(3) => {
+                self.types.insert(operands[0], Type::Matrix {
+                    column: operands[1],
+                    count: operands[2],
+                });
+            }
+            op::TYPE_IMAGE if min(8) => {
+                self.types.insert(operands[0], Type::Image {
+                    dim: operands[2],
+                    sampled: operands[6],
+                });
+            }
+            op::TYPE_SAMPLER if min(1) => {
+                self.types.insert(operands[0], Type::Sampler);
+            }
+            op::TYPE_SAMPLED_IMAGE if min(2) => {
+                self.types.insert(operands[0], Type::SampledImage);
+            }
+            op::TYPE_ARRAY if min(3) => {
+                self.types.insert(operands[0], Type::Array {
+                    element: operands[1],
+                    length: operands[2],
+                });
+            }
+            op::TYPE_RUNTIME_ARRAY if min(2) => {
+                self.types.insert(operands[0], Type::RuntimeArray {
+                    element: operands[1],
+                });
+            }
+            op::TYPE_STRUCT if min(1) => {
+                self.types.insert(operands[0], Type::Struct {
+                    members: operands[1..].to_vec(),
+                });
+            }
+            op::TYPE_POINTER if min(3) => {
+                self.types.insert(operands[0], Type::Pointer {
+                    pointee: operands[2],
+                });
+            }
+            op::TYPE_ACCELERATION_STRUCTURE if min(1) => {
+                self.types.insert(operands[0], Type::AccelerationStructure);
+            }
+            op::CONSTANT if min(2) => {
+                self.constants.insert(operands[1], operands[2..].to_vec());
+            }
+            op::CONSTANT_COMPOSITE | op::SPEC_CONSTANT_COMPOSITE if min(2) => {
+                self.composites.insert(operands[1], operands[2..].to_vec());
+            }
+            op::SPEC_CONSTANT_TRUE | op::SPEC_CONSTANT_FALSE if min(2) => {
+                self.spec_constants.push(SpecConstant {
+                    ty: operands[0],
+                    id: operands[1],
+                    default: vec![(opcode == op::SPEC_CONSTANT_TRUE) as u32],
+                });
+            }
+            op::SPEC_CONSTANT if min(2) => {
+                self.spec_constants.push(SpecConstant {
+                    ty: operands[0],
+                    id: operands[1],
+                    default: operands[2..].to_vec(),
+                });
+            }
+            op::VARIABLE if min(3) => {
+                self.variables.push(Variable {
+                    ty: operands[0],
+                    id: operands[1],
+                    storage_class: operands[2],
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// First operand of a decoration, or `0` for decorations without operands.
+    pub fn decoration(&self, id: u32, decoration: u32) -> Option<u32> {
+        self.decorations
+            .get(&id)?
+            .iter()
+            .find(|(d, _)| *d == decoration)
+            .map(|(_, operands)| operands.first().copied().unwrap_or(0))
+    }
+
+    /// First operand of a member decoration, or `0` for decorations without
+    /// operands.
+    pub fn member_decoration(&self, ty: u32, member: u32, decoration: u32) -> Option<u32> {
+        self.member_decorations
+            .get(&(ty, member))?
+            .iter()
+            .find(|(d, _)| *d == decoration)
+            .map(|(_, operands)| operands.first().copied().unwrap_or(0))
+    }
+
+    pub fn has_decoration(&self, id: u32, decoration: u32) -> bool {
+        self.decoration(id, decoration).is_some()
+    }
+
+    pub fn constant_u32(&self, id: u32) -> Option<u32> {
+        self.constants.get(&id)?.first().copied()
+    }
+
+    pub fn execution_mode(&self, entry_point: u32, mode: u32) -> Option<&[u32]> {
+        self.execution_modes
+            .iter()
+            .find(|(entry, m, _)| *entry == entry_point && *m == mode)
+            .map(|(_, _, operands)| operands.as_slice())
+    }
+
+    /// Whether `entry_point` uses a global variable. Since SPIR-V 1.4 the
+    /// interface lists every global it uses, before it only lists inputs and
+    /// outputs. The other variables then belong to the single entry point of
+    /// the module, or are looked up in the static call tree of the entry point.
+    pub fn uses_variable(&self, entry_point: &EntryPoint, variable: u32) -> bool {
+        if entry_point.interface.contains(&variable) {
+            return true;
+        }
+        if self.version >= (1, 4) {
+            return false;
+        }
+        if self.entry_points.len() == 1 {
+            return true;
+        }
+
+        self.static_call_tree(entry_point.id)
+            .iter()
+            .filter_map(|function| self.functions.get(function))
+            .any(|function| function.references.contains(&variable))
+    }
+
+    /// `function` and every function it calls, directly or not.
+    fn static_call_tree(&self, function: u32) -> HashSet<u32> {
+        let mut visited = HashSet::new();
+        let mut pending = vec![function];
+        while let Some(function) = pending.pop() {
+            if !visited.insert(function) {
+                continue;
+            }
+            if let Some(body) = self.functions.get(&function) {
+                pending.extend(&body.calls);
+            }
+        }
+
+        visited
+    }
+
+    pub fn pointee(&self, ty: u32) -> Option<u32> {
+        match self.types.get(&ty)? {
+            Type::Pointer { pointee } => Some(*pointee),
+            _ => None,
+        }
+    }
+}
+
+impl Function {
+    fn parse_instruction(&mut self, opcode: u16, operands: &[u32]) {
+        let ids = match opcode {
+            op::FUNCTION_CALL if operands.len() >= 3 => {
+                self.calls.push(operands[2]);
+                operands
+            }
+            // Followed by literal indices
+            op::VECTOR_SHUFFLE => &operands[..operands.len().min(4)],
+            op::COMPOSITE_EXTRACT => &operands[..operands.len().min(3)],
+            op::COMPOSITE_INSERT => &operands[..operands.len().min(4)],
+            _ => operands,
+        };
+
+        self.references.extend(ids);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use vkez_core::ash::vk;
+
+    use super::*;
+    use crate::reflect::{ScalarType, ShaderReflection};
+
+    /// `testdata/reflect.comp`, a compute shader using every kind of
+    /// resource.
+    pub(crate) fn reflect_comp() -> SpirvModule {
+        parse(include_bytes!("testdata/reflect.comp.spv"))
+    }
+
+    /// `testdata/interface.mesh`, a mesh shader with per-vertex and
+    /// per-primitive outputs.
+    pub(crate) fn interface_mesh() -> SpirvModule {
+        parse(include_bytes!("testdata/interface.mesh.spv"))
+    }
+
+    fn parse(bytes: &[u8]) -> SpirvModule {
+        let code = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect::<Vec<_>>();
+
+        SpirvModule::parse(&code).unwrap()
+    }
+
+    fn reflect(module: &SpirvModule) -> ShaderReflection {
+        ShaderReflection::reflect(module, &module.entry_points[0]).unwrap()
+    }
+
+    fn instruction(opcode: u16, operands: &[u32]) -> Vec<u32> {
+        let word_count = operands.len() as u32 + 1;
+        [word_count << 16 | opcode as u32]
+            .into_iter()
+            .chain(operands.iter().copied())
+            .collect()
+    }
+
+    /// Nul-terminated and padded to a whole word.
+    fn string(value: &str) -> Vec<u32> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize(value.len() / 4 * 4 + 4, 0);
+        bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect()
+    }
+
+    /// Compute entry points `a`, which uses `first` in a function it calls,
+    /// and `b`, which uses `second`. The interfaces are empty like before
+    /// SPIR-V 1.4.
+    fn two_entry_points(version: (u8, u8)) -> SpirvModule {
+        const OP_TYPE_VOID: u16 = 19;
+        const OP_TYPE_FUNCTION: u16 = 33;
+        const OP_LABEL: u16 = 248;
+        const OP_RETURN: u16 = 253;
+        const OP_LOAD: u16 = 61;
+        let (void, function_ty, uint, block, pointer) = (1, 2, 3, 4, 5);
+        let (first, second, a, b, helper) = (6, 7, 10, 11, 12);
+
+        let version = (version.0 as u32) << 16 | (version.1 as u32) << 8;
+        let entry_point = |id, name| {
+            let operands = [vec![execution_model::GL_COMPUTE, id], string(name)].concat();
+            instruction(op::ENTRY_POINT, &operands)
+        };
+        let body = |id, label, load| {
+            [
+                instruction(op::FUNCTION, &[void, id, 0, function_ty]),
+                instruction(OP_LABEL, &[label]),
+                load,
+                instruction(OP_RETURN, &[]),
+                instruction(op::FUNCTION_END, &[]),
+            ]
+            .concat()
+        };
+
+        let code = [
+            vec![MAGIC, version, 0, 100, 0],
+            entry_point(a, "a"),
+            entry_point(b, "b"),
+            instruction(op::DECORATE, &[first, decoration::BINDING, 0]),
+            instruction(op::DECORATE, &[second, decoration::BINDING, 1]),
+            instruction(OP_TYPE_VOID, &[void]),
+            instruction(OP_TYPE_FUNCTION, &[function_ty, void]),
+            instruction(op::TYPE_INT, &[uint, 32, 0]),
+            instruction(op::TYPE_STRUCT, &[block, uint]),
+            instruction(op::TYPE_POINTER, &[pointer, storage_class::UNIFORM, block]),
+            instruction(op::VARIABLE, &[pointer, first, storage_class::UNIFORM]),
+            instruction(op::VARIABLE, &[pointer, second, storage_class::UNIFORM]),
+            body(a, 20, instruction(op::FUNCTION_CALL, &[void, 21, helper])),
+            body(b, 30, instruction(OP_LOAD, &[block, 31, second])),
+            body(helper, 40, instruction(OP_LOAD, &[block, 41, first])),
+        ]
+        .concat();
+
+        SpirvModule::parse(&code).unwrap()
+    }
+
+    #[test]
+    fn variables_of_the_static_call_tree() {
+        let module = two_entry_points((1, 0));
+        let [a, b] = module.entry_points.as_slice() else {
+            panic!("Expected two entry points");
+        };
+
+        assert!(module.uses_variable(a, 6));
+        assert!(!module.uses_variable(a, 7));
+        assert!(!module.uses_variable(b, 6));
+        assert!(module.uses_variable(b, 7));
+    }
+
+    #[test]
+    fn variables_of_the_interface() {
+        // The interface is authoritative since SPIR-V 1.4
+        let module = two_entry_points((1, 4));
+        let [a, b] = module.entry_points.as_slice() else {
+            panic!("Expected two entry points");
+        };
+
+        assert!(!module.uses_variable(a, 6));
+        assert!(!module.uses_variable(b, 7));
+    }
+
+    #[test]
+    fn parse_entry_point() {
+        let module = reflect_comp();
+
+        assert_eq!(module.version, (1, 5));
+        let [entry_point] = module.entry_points.as_slice() else {
+            panic!("Expected a single entry point");
+        };
+        assert_eq!(entry_point.name, "main");
+        assert_eq!(entry_point.execution_model, execution_model::GL_COMPUTE);
+        assert_eq!(
+            module.execution_mode(entry_point.id, execution_mode::LOCAL_SIZE),
+            Some([1, 4, 1].as_slice())
+        );
+    }
+
+    #[test]
+    fn reflect_bindings() {
+        let reflection = reflect(&reflect_comp());
+
+        let bindings = reflection
+            .descriptor_bindings
+            .iter()
+            .map(|b| {
+                (
+                    b.name.as_str(),
+                    b.set,
+                    b.binding,
+                    b.descriptor_type,
+                    b.count,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(bindings, [
+            ("params", 0, 0, vk::DescriptorType::UNIFORM_BUFFER, 1),
+            ("data", 0, 1, vk::DescriptorType::STORAGE_BUFFER, 1),
+            (
+                "textures",
+                1,
+                2,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                2
+            ),
+            ("unused_image", 1, 3, vk::DescriptorType::STORAGE_IMAGE, 1),
+        ]);
+        assert!(reflection
+            .descriptor_bindings
+            .iter()
+            .all(|b| b.stages == vk::ShaderStageFlags::COMPUTE));
+    }
+
+    #[test]
+    fn reflect_push_constants() {
+        let push_constants = reflect(&reflect_comp()).push_constants.unwrap();
+
+        assert_eq!(push_constants.layout.name, "Push");
+        assert_eq!(push_constants.stages, vk::ShaderStageFlags::COMPUTE);
+        // `mat4` then `uint`
+        assert_eq!(push_constants.range(), (0, 68));
+    }
+
+    #[test]
+    fn reflect_buffer_blocks() {
+        let reflection = reflect(&reflect_comp());
+
+        let buffer_blocks = reflection
+            .buffer_blocks
+            .iter()
+            .map(|block| (block.set, block.binding, block.layout.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(buffer_blocks, [(0, 0, "Params"), (0, 1, "Data")]);
+    }
+
+    #[test]
+    fn reflect_specialization_constants() {
+        let reflection = reflect(&reflect_comp());
+
+        let constants = reflection
+            .specialization_constants
+            .iter()
+            .map(|c| (c.constant_id, c.name.as_str(), c.ty, c.default.as_slice()))
+            .collect::<Vec<_>>();
+        let uint = ScalarType::Int {
+            width: 32,
+            signed: false,
+        };
+        assert_eq!(constants, [
+            (0, "GROUP_WIDTH", uint, [64].as_slice()),
+            (
+                1,
+                "SCALE",
+                ScalarType::Float { width: 32 },
+                [1.5f32.to_bits()].as_slice()
+            ),
+            (2, "ENABLED", ScalarType::Bool, [1].as_slice()),
+            // `local_size_x_id` declares an unnamed constant
+            (3, "constant_3", uint, [1].as_slice()),
+        ]);
+    }
+
+    #[test]
+    fn reflect_local_size() {
+        let local_size = reflect(&reflect_comp())
+            .local_size
+            .unwrap()
+            .map(|component| (component.value, component.constant_id));
+
+        assert_eq!(local_size, [(1, Some(3)), (4, None), (1, None)]);
+    }
+}
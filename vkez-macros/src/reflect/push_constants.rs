@@ -0,0 +1,64 @@
+use vkez_core::ash::vk;
+
+use super::{execution_model_stage, storage_class, EntryPoint, SpirvModule, StructLayout};
+
+#[derive(Debug, Clone)]
+pub(crate) struct PushConstantBlock {
+    pub layout: StructLayout,
+    pub stages: vk::ShaderStageFlags,
+}
+
+impl PushConstantBlock {
+    /// Offset and size of the range, from the first member to the end of the
+    /// last one.
+    pub fn range(&self) -> (u32, u32) {
+        let offset = self
+            .layout
+            .members
+            .iter()
+            .map(|member| member.offset)
+            .min()
+            .unwrap_or(0);
+        // Push constant ranges are made of whole words
+        let size = (self.layout.size() - offset).div_ceil(4) * 4;
+
+        (offset, size)
+    }
+}
+
+pub(crate) fn reflect_push_constants(
+    module: &SpirvModule, entry_point: &EntryPoint,
+) -> Result<Option<PushConstantBlock>, String> {
+    let mut block = None::<PushConstantBlock>;
+
+    for variable in &module.variables {
+        if variable.storage_class != storage_class::PUSH_CONSTANT
+            || !module.uses_variable(entry_point, variable.id)
+        {
+            continue;
+        }
+
+        let Some(ty) = module.pointee(variable.ty) else {
+            continue;
+        };
+
+        // An entry point uses a single block, but before SPIR-V 1.4 the blocks
+        // of the other entry points can't be told apart
+        if let Some(block) = block.as_ref() {
+            if block.layout.id != ty {
+                return Err(
+                    "Entry points with different push constant blocks are not supported"
+                        .to_string(),
+                );
+            }
+            continue;
+        }
+
+        block = Some(PushConstantBlock {
+            layout: module.struct_layout(ty)?,
+            stages: execution_model_stage(entry_point.execution_model),
+        });
+    }
+
+    Ok(block)
+}
@@ -0,0 +1,64 @@
+use super::{decoration, ScalarType, SpirvModule};
+
+/// `layout(constant_id = N) const T name = default;`
+#[derive(Debug, Clone)]
+pub(crate) struct SpecializationConstant {
+    pub constant_id: u32,
+    pub name: String,
+    pub ty: ScalarType,
+    pub default: Vec<u32>,
+}
+
+pub(crate) fn reflect_specialization_constants(
+    module: &SpirvModule,
+) -> Result<Vec<SpecializationConstant>, String> {
+    let mut constants = Vec::<SpecializationConstant>::new();
+
+    for constant in &module.spec_constants {
+        // Constants without an id are computed from the others
+        let Some(constant_id) = module.decoration(constant.id, decoration::SPEC_ID) else {
+            continue;
+        };
+
+        let name = module
+            .names
+            .get(&constant.id)
+            .filter(|name| !name.is_empty())
+            .cloned()
+            .unwrap_or_else(|| format!("constant_{constant_id}"));
+
+        let ty = match module.scalar_type(constant.ty)? {
+            ty @ (ScalarType::Bool
+            | ScalarType::Int { width: 32 | 64, .. }
+            | ScalarType::Float { width: 32 | 64 }) => ty,
+            _ => {
+                return Err(format!(
+                    "Unsupported type for the specialization constant `{name}`"
+                ))
+            }
+        };
+
+        if constants.iter().any(|c| c.constant_id == constant_id) {
+            return Err(format!(
+                "Specialization constant id {constant_id} is used more than once"
+            ));
+        }
+
+        constants.push(SpecializationConstant {
+            constant_id,
+            name,
+            ty,
+            default: constant.default.clone(),
+        });
+    }
+
+    constants.sort_by_key(|c| c.constant_id);
+    Ok(constants)
+}
+
+impl SpecializationConstant {
+    /// Size in the specialization data, booleans are `VkBool32`.
+    pub fn size(&self) -> u32 {
+        self.ty.size()
+    }
+}
@@ -0,0 +1,129 @@
+use vkez_core::ash::vk;
+
+use super::{
+    decoration, execution_model, storage_class, EntryPoint, ScalarType, SpirvModule, Type,
+};
+
+/// `layout(location = N) in T name;` of a vertex shader.
+#[derive(Debug, Clone)]
+pub(crate) struct VertexInput {
+    pub location: u32,
+    pub format: vk::Format,
+}
+
+pub(crate) fn reflect_vertex_inputs(
+    module: &SpirvModule, entry_point: &EntryPoint,
+) -> Result<Vec<VertexInput>, String> {
+    if entry_point.execution_model != execution_model::VERTEX {
+        return Ok(Vec::new());
+    }
+
+    let mut inputs = Vec::new();
+    for variable in &module.variables {
+        if variable.storage_class != storage_class::INPUT
+            || !entry_point.interface.contains(&variable.id)
+            || module.has_decoration(variable.id, decoration::BUILT_IN)
+        {
+            continue;
+        }
+
+        let name = module.names.get(&variable.id).cloned().unwrap_or_default();
+        let Some(location) = module.decoration(variable.id, decoration::LOCATION) else {
+            return Err(format!("Vertex input `{name}` has no location"));
+        };
+        let Some(ty) = module.pointee(variable.ty) else {
+            continue;
+        };
+
+        // Matrices and arrays take one location per column or element
+        let (ty, locations) = match module.types.get(&ty) {
+            Some(Type::Matrix { column, count }) => (*column, *count),
+            Some(Type::Array { element, length }) => (
+                *element,
+                module
+                    .constant_u32(*length)
+                    .ok_or_else(|| format!("Unsupported array length for `{name}`"))?,
+            ),
+            _ => (ty, 1),
+        };
+
+        let format = vertex_format(module, ty)
+            .ok_or_else(|| format!("Unsupported type for the vertex input `{name}`"))?;
+        for i in 0..locations {
+            inputs.push(VertexInput {
+                location: location + i,
+                format,
+            });
+        }
+    }
+
+    inputs.sort_by_key(|input| input.location);
+    Ok(inputs)
+}
+
+fn vertex_format(module: &SpirvModule, ty: u32) -> Option<vk::Format> {
+    let (scalar, count) = match module.types.get(&ty)? {
+        Type::Vector { component, count } => (module.scalar_type(*component).ok()?, *count),
+        _ => (module.scalar_type(ty).ok()?, 1),
+    };
+
+    let formats = match scalar {
+        ScalarType::Float { width: 16 } => [
+            vk::Format::R16_SFLOAT,
+            vk::Format::R16G16_SFLOAT,
+            vk::Format::R16G16B16_SFLOAT,
+            vk::Format::R16G16B16A16_SFLOAT,
+        ],
+        ScalarType::Float { width: 32 } => [
+            vk::Format::R32_SFLOAT,
+            vk::Format::R32G32_SFLOAT,
+            vk::Format::R32G32B32_SFLOAT,
+            vk::Format::R32G32B32A32_SFLOAT,
+        ],
+        ScalarType::Float { width: 64 } => [
+            vk::Format::R64_SFLOAT,
+            vk::Format::R64G64_SFLOAT,
+            vk::Format::R64G64B64_SFLOAT,
+            vk::Format::R64G64B64A64_SFLOAT,
+        ],
+        ScalarType::Int {
+            width: 32,
+            signed: true,
+        } => [
+            vk::Format::R32_SINT,
+            vk::Format::R32G32_SINT,
+            vk::Format::R32G32B32_SINT,
+            vk::Format::R32G32B32A32_SINT,
+        ],
+        ScalarType::Int {
+            width: 32,
+            signed: false,
+        } => [
+            vk::Format::R32_UINT,
+            vk::Format::R32G32_UINT,
+            vk::Format::R32G32B32_UINT,
+            vk::Format::R32G32B32A32_UINT,
+        ],
+        ScalarType::Int {
+            width: 64,
+            signed: true,
+        } => [
+            vk::Format::R64_SINT,
+            vk::Format::R64G64_SINT,
+            vk::Format::R64G64B64_SINT,
+            vk::Format::R64G64B64A64_SINT,
+        ],
+        ScalarType::Int {
+            width: 64,
+            signed: false,
+        } => [
+            vk::Format::R64_UINT,
+            vk::Format::R64G64_UINT,
+            vk::Format::R64G64B64_UINT,
+            vk::Format::R64G64B64A64_UINT,
+        ],
+        _ => return None,
+    };
+
+    formats.get(count.checked_sub(1)? as usize).copied()
+}
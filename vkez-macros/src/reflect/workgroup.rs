@@ -0,0 +1,76 @@
+use super::{built_in, decoration, execution_mode, execution_model, EntryPoint, SpirvModule};
+
+/// Component of the workgroup size, set by a specialization constant when
+/// `constant_id` is some.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LocalSizeComponent {
+    pub value: u32,
+    pub constant_id: Option<u32>,
+}
+
+pub(crate) type LocalSize = [LocalSizeComponent; 3];
+
+/// Workgroup size of a compute, task or mesh entry point.
+pub(crate) fn reflect_local_size(
+    module: &SpirvModule, entry_point: &EntryPoint,
+) -> Result<Option<LocalSize>, String> {
+    if !matches!(
+        entry_point.execution_model,
+        execution_model::GL_COMPUTE
+            | execution_model::TASK_NV
+            | execution_model::MESH_NV
+            | execution_model::TASK_EXT
+            | execution_model::MESH_EXT
+    ) {
+        return Ok(None);
+    }
+
+    // The `WorkgroupSize` built-in overrides the execution modes
+    let workgroup_size = module.composites.iter().find(|(id, _)| {
+        module.decoration(**id, decoration::BUILT_IN) == Some(built_in::WORKGROUP_SIZE)
+    });
+
+    let ids = if let Some((_, constituents)) = workgroup_size {
+        constituents.as_slice()
+    } else if let Some(ids) = module.execution_mode(entry_point.id, execution_mode::LOCAL_SIZE_ID) {
+        ids
+    } else if let Some(sizes) = module.execution_mode(entry_point.id, execution_mode::LOCAL_SIZE) {
+        let [x, y, z] = sizes else {
+            return Err("Invalid LocalSize execution mode".to_string());
+        };
+        return Ok(Some([*x, *y, *z].map(|value| LocalSizeComponent {
+            value,
+            constant_id: None,
+        })));
+    } else {
+        return Ok(None);
+    };
+
+    let [x, y, z] = ids else {
+        return Err("Invalid workgroup size".to_string());
+    };
+
+    let component = |id: u32| -> Result<LocalSizeComponent, String> {
+        if let Some(value) = module.constant_u32(id) {
+            return Ok(LocalSizeComponent {
+                value,
+                constant_id: None,
+            });
+        }
+
+        let constant = module
+            .spec_constants
+            .iter()
+            .find(|constant| constant.id == id)
+            .ok_or_else(|| {
+                "Workgroup sizes computed from other constants are not supported".to_string()
+            })?;
+
+        Ok(LocalSizeComponent {
+            value: constant.default.first().copied().unwrap_or(1),
+            constant_id: module.decoration(id, decoration::SPEC_ID),
+        })
+    };
+
+    Ok(Some([component(*x)?, component(*y)?, component(*z)?]))
+}
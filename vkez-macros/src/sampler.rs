@@ -0,0 +1,227 @@
+use std::fmt::Debug;
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use structmeta::StructMeta;
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream},
+    Ident, LitFloat,
+};
+use vkez_core::ash::vk;
+
+use crate::tokens::variant_tokens;
+
+/// `vk::SamplerCreateInfo` of an immutable sampler, variants are given by name
+/// like `filter = LINEAR, address_mode = CLAMP_TO_EDGE, max_anisotropy = 16.0`.
+#[derive(StructMeta, Default, Debug)]
+pub(crate) struct SamplerArgs {
+    /// Both `mag_filter` and `min_filter`.
+    filter: Option<Ident>,
+    mag_filter: Option<Ident>,
+    min_filter: Option<Ident>,
+    mipmap_mode: Option<Ident>,
+    /// The three address modes.
+    address_mode: Option<Ident>,
+    address_mode_u: Option<Ident>,
+    address_mode_v: Option<Ident>,
+    address_mode_w: Option<Ident>,
+    mip_lod_bias: Option<LitFloat>,
+    /// Enables anisotropic filtering.
+    max_anisotropy: Option<LitFloat>,
+    /// Enables comparison.
+    compare_op: Option<Ident>,
+    min_lod: Option<LitFloat>,
+    /// Defaults to `vk::LOD_CLAMP_NONE`.
+    max_lod: Option<LitFloat>,
+    border_color: Option<Ident>,
+    unnormalized_coordinates: bool,
+}
+
+/// `binding_name(filter = LINEAR, ...)` in `#[descriptor_set]`, bindings are
+/// named as in the shader.
+#[derive(Debug)]
+pub(crate) struct NamedSamplerArgs {
+    pub name: Ident,
+    pub args: SamplerArgs,
+}
+
+impl Parse for NamedSamplerArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        let content;
+        parenthesized!(content in input);
+        let args = content.parse()?;
+
+        Ok(Self { name, args })
+    }
+}
+
+impl SamplerArgs {
+    pub fn create_info(&self) -> Result<vk::SamplerCreateInfo, TokenStream2> {
+        const FILTERS: &[vk::Filter] = &[
+            vk::Filter::NEAREST,
+            vk::Filter::LINEAR,
+            vk::Filter::CUBIC_EXT,
+        ];
+        const ADDRESS_MODES: &[vk::SamplerAddressMode] = &[
+            vk::SamplerAddressMode::REPEAT,
+            vk::SamplerAddressMode::MIRRORED_REPEAT,
+            vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            vk::SamplerAddressMode::CLAMP_TO_BORDER,
+            vk::SamplerAddressMode::MIRROR_CLAMP_TO_EDGE,
+        ];
+
+        let filter = |specific: &Option<Ident>| {
+            specific
+                .as_ref()
+                .or(self.filter.as_ref())
+                .map_or(Ok(vk::Filter::NEAREST), |name| parse_variant(name, FILTERS))
+        };
+        let address_mode = |specific: &Option<Ident>| {
+            specific
+                .as_ref()
+                .or(self.address_mode.as_ref())
+                .map_or(Ok(vk::SamplerAddressMode::REPEAT), |name| {
+                    parse_variant(name, ADDRESS_MODES)
+                })
+        };
+
+        let mipmap_mode =
+            self.mipmap_mode
+                .as_ref()
+                .map_or(Ok(vk::SamplerMipmapMode::NEAREST), |name| {
+                    parse_variant(name, &[
+                        vk::SamplerMipmapMode::NEAREST,
+                        vk::SamplerMipmapMode::LINEAR,
+                    ])
+                })?;
+        let compare_op = self
+            .compare_op
+            .as_ref()
+            .map(|name| {
+                parse_variant(name, &[
+                    vk::CompareOp::NEVER,
+                    vk::CompareOp::LESS,
+                    vk::CompareOp::EQUAL,
+                    vk::CompareOp::LESS_OR_EQUAL,
+                    vk::CompareOp::GREATER,
+                    vk::CompareOp::NOT_EQUAL,
+                    vk::CompareOp::GREATER_OR_EQUAL,
+                    vk::CompareOp::ALWAYS,
+                ])
+            })
+            .transpose()?;
+        let border_color = self.border_color.as_ref().map_or(
+            Ok(vk::BorderColor::FLOAT_TRANSPARENT_BLACK),
+            |name| {
+                parse_variant(name, &[
+                    vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
+                    vk::BorderColor::INT_TRANSPARENT_BLACK,
+                    vk::BorderColor::FLOAT_OPAQUE_BLACK,
+                    vk::BorderColor::INT_OPAQUE_BLACK,
+                    vk::BorderColor::FLOAT_OPAQUE_WHITE,
+                    vk::BorderColor::INT_OPAQUE_WHITE,
+                ])
+            },
+        )?;
+        let max_anisotropy = parse_float(&self.max_anisotropy)?;
+        let bool32 = |value: bool| if value { vk::TRUE } else { vk::FALSE };
+
+        Ok(vk::SamplerCreateInfo {
+            mag_filter: filter(&self.mag_filter)?,
+            min_filter: filter(&self.min_filter)?,
+            mipmap_mode,
+            address_mode_u: address_mode(&self.address_mode_u)?,
+            address_mode_v: address_mode(&self.address_mode_v)?,
+            address_mode_w: address_mode(&self.address_mode_w)?,
+            mip_lod_bias: parse_float(&self.mip_lod_bias)?.unwrap_or(0.0),
+            anisotropy_enable: bool32(max_anisotropy.is_some()),
+            max_anisotropy: max_anisotropy.unwrap_or(0.0),
+            compare_enable: bool32(compare_op.is_some()),
+            compare_op: compare_op.unwrap_or(vk::CompareOp::NEVER),
+            min_lod: parse_float(&self.min_lod)?.unwrap_or(0.0),
+            max_lod: parse_float(&self.max_lod)?.unwrap_or(vk::LOD_CLAMP_NONE),
+            border_color,
+            unnormalized_coordinates: bool32(self.unnormalized_coordinates),
+            ..Default::default()
+        })
+    }
+}
+
+pub(crate) fn sampler_create_info_tokens(info: &vk::SamplerCreateInfo) -> TokenStream2 {
+    let mag_filter = variant_tokens("Filter", info.mag_filter);
+    let min_filter = variant_tokens("Filter", info.min_filter);
+    let mipmap_mode = variant_tokens("SamplerMipmapMode", info.mipmap_mode);
+    let address_modes = [
+        info.address_mode_u,
+        info.address_mode_v,
+        info.address_mode_w,
+    ]
+    .map(|mode| variant_tokens("SamplerAddressMode", mode));
+    let [address_mode_u, address_mode_v, address_mode_w] = &address_modes;
+    let compare_op = variant_tokens("CompareOp", info.compare_op);
+    let border_color = variant_tokens("BorderColor", info.border_color);
+    let vk::SamplerCreateInfo {
+        mip_lod_bias,
+        anisotropy_enable,
+        max_anisotropy,
+        compare_enable,
+        min_lod,
+        max_lod,
+        unnormalized_coordinates,
+        ..
+    } = *info;
+
+    quote! {
+        ::vkez_core::ash::vk::SamplerCreateInfo {
+            s_type: ::vkez_core::ash::vk::StructureType::SAMPLER_CREATE_INFO,
+            p_next: ::std::ptr::null(),
+            flags: ::vkez_core::ash::vk::SamplerCreateFlags::empty(),
+            mag_filter: #mag_filter,
+            min_filter: #min_filter,
+            mipmap_mode: #mipmap_mode,
+            address_mode_u: #address_mode_u,
+            address_mode_v: #address_mode_v,
+            address_mode_w: #address_mode_w,
+            mip_lod_bias: #mip_lod_bias,
+            anisotropy_enable: #anisotropy_enable,
+            max_anisotropy: #max_anisotropy,
+            compare_enable: #compare_enable,
+            compare_op: #compare_op,
+            min_lod: #min_lod,
+            max_lod: #max_lod,
+            border_color: #border_color,
+            unnormalized_coordinates: #unnormalized_coordinates,
+        }
+    }
+}
+
+fn parse_variant<T: Copy + Debug>(name: &Ident, variants: &[T]) -> Result<T, TokenStream2> {
+    let name_string = name.to_string();
+    match variants
+        .iter()
+        .find(|variant| format!("{variant:?}") == name_string)
+    {
+        Some(variant) => Ok(*variant),
+        None => {
+            let expected = variants
+                .iter()
+                .map(|variant| format!("{variant:?}"))
+                .collect::<Vec<_>>();
+            Err(syn::Error::new_spanned(
+                name,
+                format!("Unknown variant, expected one of {}", expected.join(", ")),
+            )
+            .to_compile_error())
+        }
+    }
+}
+
+fn parse_float(value: &Option<LitFloat>) -> Result<Option<f32>, TokenStream2> {
+    value
+        .as_ref()
+        .map(|value| value.base10_parse::<f32>())
+        .transpose()
+        .map_err(|e| e.to_compile_error())
+}
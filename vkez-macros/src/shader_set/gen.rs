@@ -0,0 +1,701 @@
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream as TokenStream2;
+use proc_macro_error::{abort_if_dirty, emit_error};
+use quote::{format_ident, quote, ToTokens};
+use syn::{Fields, Ident, Item, ItemMod, ItemStruct, LitInt};
+use vkez_core::ash::vk;
+
+use super::{
+    validate_stage_interfaces, AccumulateShaderItemsVisitor, ShaderSetArgs,
+    ShaderSetDescriptorSetAttributes, ShaderSetPipelineLayoutAttributes, ShaderSetShaderAttributes,
+};
+use crate::{
+    compiler::{
+        compile_shader, env_tracking_tokens, parse_shader_kind, CompiledShader, RelativeTo,
+        ShaderCompileArgs,
+    },
+    descriptor_set::{
+        check_bindings, gen_descriptor_set_info, gen_layout_binding, immutable_samplers_tokens,
+    },
+    layout::gen_reflected_types,
+    reflect::DescriptorBinding,
+    tokens::{
+        binding_flags_tokens, cstr_tokens, pascal_case_ident, snake_case_ident, stage_flags_tokens,
+    },
+};
+
+pub(crate) fn shader_set_impl(
+    args: ShaderSetArgs, items: AccumulateShaderItemsVisitor, mut module: ItemMod,
+) -> Result<TokenStream2, TokenStream2> {
+    let Some((_, content)) = module.content.as_mut() else {
+        emit_error!(module, "Expected module with a body");
+        return Err(quote!(#module));
+    };
+
+    let declared_idents = items
+        .shaders
+        .iter()
+        .map(|(ident, _)| ident)
+        .chain(items.descriptor_sets.iter().map(|(ident, _)| ident))
+        .chain(items.pipeline_layouts.iter().map(|(ident, _)| ident));
+    for ident in declared_idents {
+        let is_unit_struct = content.iter().any(|item| match item {
+            Item::Struct(item) => item.ident == *ident && matches!(item.fields, Fields::Unit),
+            _ => false,
+        });
+        if !is_unit_struct {
+            emit_error!(
+                ident,
+                "Shaders, descriptor sets and pipeline layouts must be declared as unit structs"
+            );
+        }
+    }
+
+    let mut generated = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut shaders = HashMap::new();
+    // With a type per entry point in place of the declared ones
+    let mut shader_idents = Vec::new();
+    for (ident, attributes) in &items.shaders {
+        let compiled = match compile_set_shader(&args, ident, attributes) {
+            Ok(compiled) => compiled,
+            Err(e) => {
+                errors.push(e);
+                shader_idents.push(ident.clone());
+                continue;
+            }
+        };
+
+        let entry_point_idents = match compiled.as_slice() {
+            [_] => vec![ident.clone()],
+            _ => match split_entry_points(content, ident, &compiled) {
+                Ok(idents) => idents,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            },
+        };
+
+        for (ident, shader) in entry_point_idents.into_iter().zip(compiled) {
+            generated.push(gen_shader_info(&ident, &shader));
+            match gen_shader_types(&ident, attributes, &shader) {
+                Ok(types) => generated.push(types),
+                Err(e) => errors.push(e),
+            }
+            shaders.insert(ident.clone(), shader);
+            shader_idents.push(ident);
+        }
+    }
+
+    let entry_point = |ident| Some((ident, &shaders.get(ident)?.compiled.entry_point));
+    let set_shaders = shader_idents
+        .iter()
+        .filter_map(entry_point)
+        .collect::<Vec<_>>();
+    // Errors of the shaders lists are reported with the pipeline layouts
+    let pipelines = items
+        .pipeline_layouts
+        .iter()
+        .filter_map(|(_, attributes)| layout_shaders(attributes, &shader_idents).ok())
+        .map(|idents| idents.into_iter().filter_map(entry_point).collect())
+        .collect::<Vec<_>>();
+    errors.extend(validate_stage_interfaces(&set_shaders, &pipelines));
+
+    // Pipeline layouts take the binding flags of the declared sets
+    let mut declared_bindings = Vec::new();
+    for (ident, attributes) in &items.descriptor_sets {
+        match reflect_set_descriptor_set(ident, attributes, &shader_idents, &shaders) {
+            Ok(Some(bindings)) => {
+                generated.push(gen_descriptor_set_info(ident, &bindings));
+                declared_bindings.extend(bindings);
+            }
+            // The shader failed to compile, the error is already reported
+            Ok(None) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+
+    for (ident, attributes) in &items.pipeline_layouts {
+        match reflect_set_pipeline_layout(
+            ident,
+            attributes,
+            &shader_idents,
+            &shaders,
+            &declared_bindings,
+        ) {
+            Ok(Some(layout)) => generated.push(gen_pipeline_layout_info(ident, &layout)),
+            // A shader failed to compile, the error is already reported
+            Ok(None) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+
+    abort_if_dirty();
+
+    for tree in generated {
+        content.push(Item::Verbatim(tree));
+    }
+
+    if errors.is_empty() {
+        Ok(quote!(#module))
+    } else {
+        Err(quote!(#module #(#errors)*))
+    }
+}
+
+pub(crate) struct ShaderState {
+    pub compiled: CompiledShader,
+}
+
+/// A shader per entry point of the module when `entry` is not given.
+fn compile_set_shader(
+    args: &ShaderSetArgs, ident: &Ident, attributes: &ShaderSetShaderAttributes,
+) -> Result<Vec<ShaderState>, TokenStream2> {
+    let Some(file) = attributes.file.as_ref() else {
+        return Err(error(ident, "Missing shader file, add `file = \"...\"`"));
+    };
+
+    let kind = match attributes.kind.as_ref() {
+        Some(kind) => match parse_shader_kind(&kind.to_string()) {
+            Some(kind) => Some(kind),
+            None => {
+                return Err(error(kind, "Unknown shader kind, see shaderc::ShaderKind"));
+            }
+        },
+        None => None,
+    };
+
+    let relative_to = RelativeTo::parse(
+        attributes
+            .relative_to
+            .as_ref()
+            .or(args.relative_to.as_ref()),
+    );
+
+    // Shader specific directories are searched first
+    let include_dirs = attributes
+        .include_dirs
+        .iter()
+        .chain(args.include_dirs.iter())
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let options = attributes.compile_options().or(args.compile_options());
+
+    let compiled = compile_shader(ShaderCompileArgs {
+        path: file,
+        relative_to,
+        kind,
+        entry: attributes.entry.as_ref(),
+        include_dirs: &include_dirs,
+        defines: attributes.defines.as_deref().unwrap_or_default(),
+        options: &options,
+    })?;
+
+    Ok(compiled
+        .into_iter()
+        .map(|compiled| ShaderState { compiled })
+        .collect())
+}
+
+/// Replaces the declared struct by a struct per entry point, named after both
+/// like `MyShaderVSMain`, with the same attributes.
+fn split_entry_points(
+    content: &mut Vec<Item>, ident: &Ident, shaders: &[ShaderState],
+) -> Result<Vec<Ident>, TokenStream2> {
+    let idents = shaders
+        .iter()
+        .map(|shader| {
+            let entry_point = &shader.compiled.entry_point.name;
+            pascal_case_ident(entry_point, ident.span())
+                .map(|entry_point| format_ident!("{ident}{entry_point}"))
+                .map_err(|e| error(ident, &e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let Some(position) = content
+        .iter()
+        .position(|item| matches!(item, Item::Struct(item) if item.ident == *ident))
+    else {
+        return Ok(idents);
+    };
+    let Item::Struct(declared) = content.remove(position) else {
+        unreachable!();
+    };
+
+    for (i, entry_point_ident) in idents.iter().enumerate() {
+        let item = ItemStruct {
+            ident: entry_point_ident.clone(),
+            ..declared.clone()
+        };
+        content.insert(position + i, Item::Struct(item));
+    }
+
+    Ok(idents)
+}
+
+fn reflect_set_descriptor_set(
+    ident: &Ident, attributes: &ShaderSetDescriptorSetAttributes, declared_shaders: &[Ident],
+    shaders: &HashMap<Ident, ShaderState>,
+) -> Result<Option<Vec<DescriptorBinding>>, TokenStream2> {
+    let Some(from_shader) = attributes.from_shader.as_ref() else {
+        return Err(error(
+            ident,
+            "Missing source shader, add `from_shader = ...`",
+        ));
+    };
+
+    let Some(shader_ident) = from_shader.segments.last().map(|s| &s.ident) else {
+        return Err(error(from_shader, "Expected a shader of this shader set"));
+    };
+
+    let Some(shader) = shaders.get(shader_ident) else {
+        return if declared_shaders.contains(shader_ident) {
+            Ok(None)
+        } else {
+            Err(error(from_shader, "Expected a shader of this shader set"))
+        };
+    };
+
+    let set = match attributes.set.as_ref() {
+        Some(set) => set
+            .base10_parse::<u32>()
+            .map_err(|e| e.to_compile_error())?,
+        None => 0,
+    };
+
+    let mut bindings = shader
+        .compiled
+        .reflection
+        .descriptor_bindings
+        .iter()
+        .filter(|b| b.set == set)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if bindings.is_empty() {
+        let mut available = shader
+            .compiled
+            .reflection
+            .descriptor_bindings
+            .iter()
+            .map(|b| b.set)
+            .collect::<Vec<_>>();
+        available.sort_unstable();
+        available.dedup();
+        let available = available
+            .iter()
+            .map(|set| set.to_string())
+            .collect::<Vec<_>>();
+
+        let message = if available.is_empty() {
+            format!("Shader `{shader_ident}` doesn't use any descriptor set")
+        } else {
+            format!(
+                "Shader `{shader_ident}` has no descriptor set {set}, available sets are: {}",
+                available.join(", ")
+            )
+        };
+
+        return Err(error(from_shader, &message));
+    }
+
+    apply_set_attributes(ident, attributes, set, &mut bindings)?;
+
+    Ok(Some(bindings))
+}
+
+fn apply_set_attributes(
+    ident: &Ident, attributes: &ShaderSetDescriptorSetAttributes, set: u32,
+    bindings: &mut [DescriptorBinding],
+) -> Result<(), TokenStream2> {
+    let find_binding = |bindings: &[DescriptorBinding], lit: &LitInt| {
+        let number = lit
+            .base10_parse::<u32>()
+            .map_err(|e| e.to_compile_error())?;
+        bindings
+            .iter()
+            .position(|b| b.binding == number)
+            .ok_or_else(|| {
+                error(
+                    lit,
+                    &format!("Descriptor set {set} has no binding {number}"),
+                )
+            })
+    };
+
+    let lists = [
+        (
+            &attributes.partially_bound,
+            vk::DescriptorBindingFlags::PARTIALLY_BOUND,
+        ),
+        (
+            &attributes.update_after_bind,
+            vk::DescriptorBindingFlags::UPDATE_AFTER_BIND,
+        ),
+        (
+            &attributes.update_unused_while_pending,
+            vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING,
+        ),
+    ];
+    for (list, flag) in lists {
+        for lit in list.iter().flatten() {
+            bindings[find_binding(bindings, lit)?].flags |= flag;
+        }
+    }
+
+    if let Some(variable_count) = attributes.variable_count.as_ref() {
+        let binding = &mut bindings[find_binding(bindings, &variable_count.args.binding)?];
+        binding.flags |= vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT;
+        if let Some(max) = variable_count.args.max.as_ref() {
+            binding.count = match max.base10_parse::<u32>() {
+                Ok(0) => return Err(error(max, "Maximum count must be at least 1")),
+                Ok(max) => max,
+                Err(e) => return Err(e.to_compile_error()),
+            };
+        }
+    }
+
+    for sampler in attributes.immutable_samplers.iter().flatten() {
+        let Some(binding) = bindings.iter_mut().find(|b| sampler.name == b.name) else {
+            let names = bindings
+                .iter()
+                .map(|b| format!("`{}`", b.name))
+                .collect::<Vec<_>>();
+            return Err(error(
+                &sampler.name,
+                &format!(
+                    "Descriptor set {set} has no binding with this name, expected one of {}",
+                    names.join(", ")
+                ),
+            ));
+        };
+        binding.immutable_sampler = Some(sampler.args.create_info()?);
+    }
+
+    if let Some(runtime_array) = bindings.iter().find(|b| b.count == 0) {
+        return Err(error(
+            ident,
+            &format!(
+                "Runtime-sized array `{}` at binding {} needs a maximum count, add \
+                 `variable_count(binding = {}, max = ...)`",
+                runtime_array.name, runtime_array.binding, runtime_array.binding
+            ),
+        ));
+    }
+
+    check_bindings(bindings).map_err(|e| error(ident, &e))
+}
+
+pub(crate) struct PipelineLayout {
+    /// Bindings of every set up to the last one used, in set index order.
+    pub sets: Vec<Vec<DescriptorBinding>>,
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
+}
+
+fn layout_shaders<'a>(
+    attributes: &'a ShaderSetPipelineLayoutAttributes, declared_shaders: &'a [Ident],
+) -> Result<Vec<&'a Ident>, TokenStream2> {
+    match attributes.shaders.as_ref() {
+        Some(paths) => paths
+            .iter()
+            .map(|path| match path.segments.last() {
+                Some(segment) if declared_shaders.contains(&segment.ident) => Ok(&segment.ident),
+                _ => Err(error(path, "Expected a shader of this shader set")),
+            })
+            .collect(),
+        None => Ok(declared_shaders.iter().collect()),
+    }
+}
+
+fn reflect_set_pipeline_layout(
+    ident: &Ident, attributes: &ShaderSetPipelineLayoutAttributes, declared_shaders: &[Ident],
+    shaders: &HashMap<Ident, ShaderState>, declared_bindings: &[DescriptorBinding],
+) -> Result<Option<PipelineLayout>, TokenStream2> {
+    let shader_idents = layout_shaders(attributes, declared_shaders)?;
+    if shader_idents.is_empty() {
+        return Err(error(ident, "Pipeline layout without shaders"));
+    }
+
+    let mut bindings = Vec::<(&Ident, DescriptorBinding)>::new();
+    // Union of the ranges of each stage, Vulkan allows a single range per stage
+    let mut stage_ranges = Vec::<(vk::ShaderStageFlags, u32, u32)>::new();
+
+    for shader_ident in shader_idents {
+        let Some(shader) = shaders.get(shader_ident) else {
+            return Ok(None);
+        };
+        let stage = shader.compiled.entry_point.stage;
+        let reflection = &shader.compiled.reflection;
+
+        for binding in &reflection.descriptor_bindings {
+            match bindings
+                .iter_mut()
+                .find(|(_, b)| b.set == binding.set && b.binding == binding.binding)
+            {
+                Some((_, existing))
+                    if existing.descriptor_type == binding.descriptor_type
+                        && existing.count == binding.count =>
+                {
+                    existing.stages |= stage;
+                }
+                Some((other_ident, existing)) => {
+                    return Err(error(
+                        ident,
+                        &format!(
+                            "Conflicting declarations of set = {}, binding = {}: {} in `{}`, {} \
+                             in `{}`",
+                            binding.set,
+                            binding.binding,
+                            describe_binding(existing),
+                            other_ident,
+                            describe_binding(binding),
+                            shader_ident,
+                        ),
+                    ));
+                }
+                None => bindings.push((shader_ident, DescriptorBinding {
+                    stages: stage,
+                    ..binding.clone()
+                })),
+            }
+        }
+
+        if let Some(block) = reflection.push_constants.as_ref() {
+            let (offset, size) = block.range();
+            match stage_ranges.iter_mut().find(|(s, ..)| *s == stage) {
+                Some((_, start, end)) => {
+                    *start = (*start).min(offset);
+                    *end = (*end).max(offset + size);
+                }
+                None => stage_ranges.push((stage, offset, offset + size)),
+            }
+        }
+    }
+
+    let set_count = bindings.iter().map(|(_, b)| b.set + 1).max().unwrap_or(0);
+    let mut sets = vec![Vec::new(); set_count as usize];
+    for (_, mut binding) in bindings {
+        if let Some(declared) = declared_bindings.iter().find(|declared| {
+            declared.set == binding.set
+                && declared.binding == binding.binding
+                && declared.descriptor_type == binding.descriptor_type
+        }) {
+            binding.flags = declared.flags;
+            binding.immutable_sampler = declared.immutable_sampler;
+            if binding.count == 0
+                || declared
+                    .flags
+                    .contains(vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT)
+            {
+                binding.count = declared.count;
+            }
+        }
+
+        if binding.count == 0 {
+            return Err(error(
+                ident,
+                &format!(
+                    "Runtime-sized array `{}` at set = {}, binding = {} needs a maximum count, \
+                     declare the set with `#[descriptor_set(..., variable_count(binding = {}, max \
+                     = ...))]`",
+                    binding.name, binding.set, binding.binding, binding.binding
+                ),
+            ));
+        }
+
+        sets[binding.set as usize].push(binding);
+    }
+    for set in &mut sets {
+        set.sort_by_key(|b| b.binding);
+    }
+
+    // Stages sharing the same range share a single entry
+    let mut push_constant_ranges = Vec::<vk::PushConstantRange>::new();
+    for (stage, start, end) in stage_ranges {
+        match push_constant_ranges
+            .iter_mut()
+            .find(|range| range.offset == start && range.size == end - start)
+        {
+            Some(range) => range.stage_flags |= stage,
+            None => push_constant_ranges.push(vk::PushConstantRange {
+                stage_flags: stage,
+                offset: start,
+                size: end - start,
+            }),
+        }
+    }
+
+    Ok(Some(PipelineLayout {
+        sets,
+        push_constant_ranges,
+    }))
+}
+
+fn describe_binding(binding: &DescriptorBinding) -> String {
+    match binding.count {
+        0 => format!("{:?}[]", binding.descriptor_type),
+        1 => format!("{:?}", binding.descriptor_type),
+        count => format!("{:?}[{count}]", binding.descriptor_type),
+    }
+}
+
+fn error(tokens: impl ToTokens, message: &str) -> TokenStream2 {
+    syn::Error::new_spanned(tokens, message).to_compile_error()
+}
+
+fn gen_shader_info(ident: &Ident, shader: &ShaderState) -> TokenStream2 {
+    let path = shader.compiled.absolute_path.to_string_lossy();
+    let includes = shader.compiled.includes.iter().map(|p| p.to_string_lossy());
+    let code = shader.compiled.code_tokens();
+    let content_hash = &shader.compiled.content_hash;
+    let env_tracking = env_tracking_tokens();
+    let stage = stage_flags_tokens(shader.compiled.entry_point.stage);
+    let entry_point = cstr_tokens(&shader.compiled.entry_point.name, ident.span());
+
+    quote! {
+        const _: &'static [u8] = include_bytes!(#path);
+        #(const _: &'static str = include_str!(#includes);)*
+        #env_tracking
+
+        unsafe impl ::vkez_core::shaders::RawShaderInfo for #ident {
+            const VIBE_CHECK: &'static str = #content_hash;
+            const CODE: &'static [u32] = #code;
+            const STAGE: ::vkez_core::ash::vk::ShaderStageFlags = #stage;
+            const ENTRY_POINT: &'static ::std::ffi::CStr = #entry_point;
+        }
+    }
+}
+
+/// Types reflected from the shader, in a module named after it.
+fn gen_shader_types(
+    ident: &Ident, attributes: &ShaderSetShaderAttributes, shader: &ShaderState,
+) -> Result<TokenStream2, TokenStream2> {
+    let module = snake_case_ident(ident);
+    let types = gen_reflected_types(&shader.compiled.reflection)
+        .map_err(|e| error(attributes.file.as_ref(), &e))?;
+
+    let mut impls = Vec::new();
+    if let Some(push_constants) = types.push_constants {
+        impls.push(quote! {
+            unsafe impl ::vkez_core::shaders::ShaderPushConstants for #ident {
+                type PushConstants = #module::#push_constants;
+                const RANGE: ::vkez_core::ash::vk::PushConstantRange = #module::#push_constants::RANGE;
+            }
+        });
+    }
+    if let Some(specialization) = types.specialization {
+        impls.push(quote! {
+            unsafe impl ::vkez_core::shaders::ShaderSpecialization for #ident {
+                type Specialization = #module::#specialization;
+                const MAP_ENTRIES: &'static [::vkez_core::ash::vk::SpecializationMapEntry] =
+                    #module::#specialization::MAP_ENTRIES;
+            }
+        });
+    }
+    if let Some(inputs) = types.vertex_inputs {
+        impls.push(quote! {
+            unsafe impl ::vkez_core::vertex::VertexShader for #ident {
+                const INPUTS: &'static [::vkez_core::vertex::VertexAttribute] = #inputs;
+            }
+        });
+    }
+    if let Some(local_size) = types.local_size {
+        impls.push(quote! {
+            unsafe impl ::vkez_core::shaders::ComputeShader for #ident {
+                const LOCAL_SIZE: [u32; 3] = #local_size;
+            }
+        });
+    }
+
+    let items = types.items;
+    let module = (!items.is_empty()).then(|| {
+        quote! {
+            pub mod #module {
+                #(#items)*
+            }
+        }
+    });
+
+    Ok(quote! {
+        #module
+        #(#impls)*
+    })
+}
+
+fn gen_pipeline_layout_info(ident: &Ident, layout: &PipelineLayout) -> TokenStream2 {
+    let sets = layout.sets.iter().map(|bindings| {
+        let bindings = bindings.iter().map(gen_layout_binding);
+        quote!(&[#(#bindings),*])
+    });
+
+    let set_binding_flags = layout
+        .sets
+        .iter()
+        .flatten()
+        .any(|b| !b.flags.is_empty())
+        .then(|| {
+            let sets = layout.sets.iter().map(|bindings| {
+                if bindings.iter().all(|b| b.flags.is_empty()) {
+                    return quote!(&[]);
+                }
+                let flags = bindings.iter().map(|b| binding_flags_tokens(b.flags));
+                quote!(&[#(#flags),*])
+            });
+            quote! {
+                const SET_BINDING_FLAGS: &'static [&'static [::vkez_core::ash::vk::DescriptorBindingFlags]] = &[
+                    #(#sets),*
+                ];
+            }
+        });
+
+    let push_constant_ranges = layout.push_constant_ranges.iter().map(|range| {
+        let stages = stage_flags_tokens(range.stage_flags);
+        let offset = range.offset;
+        let size = range.size;
+        quote! {
+            ::vkez_core::ash::vk::PushConstantRange {
+                stage_flags: #stages,
+                offset: #offset,
+                size: #size,
+            }
+        }
+    });
+
+    let set_immutable_samplers = layout
+        .sets
+        .iter()
+        .flatten()
+        .any(|b| b.immutable_sampler.is_some())
+        .then(|| {
+            let sets = layout.sets.iter().map(|bindings| {
+                let immutable_samplers = bindings.iter().filter_map(immutable_samplers_tokens);
+                quote!(&[#(#immutable_samplers),*])
+            });
+            quote! {
+                const SET_IMMUTABLE_SAMPLERS: &'static [&'static [::vkez_core::descriptor_sets::ImmutableSamplers]] = &[
+                    #(#sets),*
+                ];
+            }
+        });
+
+    quote! {
+        unsafe impl ::vkez_core::pipeline_layout::RawPipelineLayoutInfo for #ident {
+            const SET_LAYOUT_BINDINGS: &'static [&'static [::vkez_core::ash::vk::DescriptorSetLayoutBinding]] = &[
+                #(#sets),*
+            ];
+
+            #set_binding_flags
+
+            #set_immutable_samplers
+
+            const PUSH_CONSTANT_RANGES: &'static [::vkez_core::ash::vk::PushConstantRange] = &[
+                #(#push_constant_ranges),*
+            ];
+        }
+    }
+}
@@ -0,0 +1,403 @@
+//! Outputs of each graphics stage of a pipeline layout must match the inputs
+//! of the next one, which the validation layers would otherwise only report
+//! when the pipeline is created.
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::Ident;
+use vkez_core::ash::vk;
+
+use crate::reflect::{InterfaceVariable, ShaderEntryPoint, StageInterface};
+
+/// Task shaders hand a payload to mesh shaders rather than located variables,
+/// mesh shaders only have outputs.
+const GRAPHICS_STAGES: [vk::ShaderStageFlags; 6] = [
+    vk::ShaderStageFlags::VERTEX,
+    vk::ShaderStageFlags::TESSELLATION_CONTROL,
+    vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+    vk::ShaderStageFlags::GEOMETRY,
+    vk::ShaderStageFlags::MESH_EXT,
+    vk::ShaderStageFlags::FRAGMENT,
+];
+
+/// Fragment inputs are checked for every shader, stages are only chained
+/// within the shaders of a pipeline layout with at most one shader per stage,
+/// the other layouts don't describe a single pipeline.
+pub(crate) fn validate_stage_interfaces(
+    shaders: &[(&Ident, &ShaderEntryPoint)], pipelines: &[Vec<(&Ident, &ShaderEntryPoint)>],
+) -> Vec<TokenStream2> {
+    let mut errors = Vec::<(&Ident, String)>::new();
+
+    for (ident, entry_point) in shaders {
+        if entry_point.stage == vk::ShaderStageFlags::FRAGMENT {
+            if let Err(e) = check_fragment_inputs(&entry_point.interface) {
+                errors.push((ident, e));
+            }
+        }
+    }
+
+    for pipeline in pipelines {
+        let stages = GRAPHICS_STAGES.map(|stage| {
+            pipeline
+                .iter()
+                .filter(|(_, entry_point)| entry_point.stage == stage)
+                .collect::<Vec<_>>()
+        });
+        if stages.iter().any(|shaders| shaders.len() > 1) {
+            continue;
+        }
+        let stages = stages.map(|shaders| shaders.first().copied());
+
+        // Tessellation needs both stages
+        match &stages {
+            [_, Some((ident, _)), None, ..] => errors.push((
+                ident,
+                "Tessellation control shader without a tessellation evaluation shader in the \
+                 pipeline layout"
+                    .to_string(),
+            )),
+            [_, None, Some((ident, _)), ..] => errors.push((
+                ident,
+                "Tessellation evaluation shader without a tessellation control shader in the \
+                 pipeline layout"
+                    .to_string(),
+            )),
+            _ => {}
+        }
+
+        for (i, consumer) in stages.iter().enumerate() {
+            let Some((consumer_ident, consumer)) = consumer else {
+                continue;
+            };
+            let Some((producer_ident, producer)) = stages[..i].iter().rev().find_map(|s| *s) else {
+                continue;
+            };
+
+            if let Err(e) = match_interfaces(
+                producer_ident,
+                &producer.interface,
+                consumer_ident,
+                &consumer.interface,
+            ) {
+                errors.push((consumer_ident, e));
+            }
+        }
+    }
+
+    // Pipeline layouts sharing shaders report the same mismatches
+    let mut reported = Vec::new();
+    errors
+        .into_iter()
+        .filter(|(ident, message)| {
+            let key = (ident.to_string(), message.clone());
+            let first = !reported.contains(&key);
+            reported.push(key);
+            first
+        })
+        .map(|(ident, message)| error(ident, &message))
+        .collect()
+}
+
+fn match_interfaces(
+    producer_ident: &Ident, producer: &StageInterface, consumer_ident: &Ident,
+    consumer: &StageInterface,
+) -> Result<(), String> {
+    for input in &consumer.inputs {
+        let Some(output) = producer.outputs.iter().find(|output| {
+            output.location == input.location && output.component == input.component
+        }) else {
+            return Err(format!(
+                "`{consumer_ident}` reads {} which `{producer_ident}` doesn't write",
+                describe(input)
+            ));
+        };
+
+        // Extra components of the output are ignored
+        if output.ty.scalar != input.ty.scalar || output.ty.components < input.ty.components {
+            return Err(format!(
+                "`{consumer_ident}` reads {} as {} but `{producer_ident}` writes {} as {}",
+                describe(input),
+                input.ty.describe(),
+                describe(output),
+                output.ty.describe()
+            ));
+        }
+
+        if output.per_primitive != input.per_primitive {
+            let per = |per_primitive| {
+                if per_primitive {
+                    "per-primitive"
+                } else {
+                    "per-vertex"
+                }
+            };
+            return Err(format!(
+                "`{consumer_ident}` reads {} {} but `{producer_ident}` writes it {}",
+                describe(input),
+                per(input.per_primitive),
+                per(output.per_primitive)
+            ));
+        }
+
+        if output.patch != input.patch {
+            let per = |patch| if patch { "per-patch" } else { "per-vertex" };
+            return Err(format!(
+                "`{consumer_ident}` reads {} {} but `{producer_ident}` writes it {}",
+                describe(input),
+                per(input.patch),
+                per(output.patch)
+            ));
+        }
+
+        // The interpolation is the one of the consumer, the qualifiers only
+        // conflict when both stages ask for a different auxiliary storage
+        if let (Some(output_auxiliary), Some(input_auxiliary)) = (
+            output.interpolation.auxiliary(),
+            input.interpolation.auxiliary(),
+        ) {
+            if output_auxiliary != input_auxiliary {
+                return Err(format!(
+                    "`{consumer_ident}` reads {} with {} interpolation but `{producer_ident}` \
+                     writes {} with {} interpolation",
+                    describe(input),
+                    input.interpolation.describe(),
+                    describe(output),
+                    output.interpolation.describe()
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn check_fragment_inputs(interface: &StageInterface) -> Result<(), String> {
+    // Per-primitive inputs are never interpolated
+    for input in &interface.inputs {
+        if input.ty.needs_flat() && !input.interpolation.flat && !input.per_primitive {
+            return Err(format!(
+                "Fragment input {} of type {} must be `flat`, integers and doubles can't be \
+                 interpolated",
+                describe(input),
+                input.ty.describe()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn describe(variable: &InterfaceVariable) -> String {
+    match variable.component {
+        0 => format!("`{}` at location {}", variable.name, variable.location),
+        component => format!(
+            "`{}` at location {}, component {component}",
+            variable.name, variable.location
+        ),
+    }
+}
+
+fn error(ident: &Ident, message: &str) -> TokenStream2 {
+    syn::Error::new(ident.span(), message).to_compile_error()
+}
+
+#[cfg(test)]
+mod tests {
+    use proc_macro2::Span;
+
+    use super::*;
+    use crate::reflect::{InterfaceType, Interpolation, ScalarType};
+
+    const VEC4: InterfaceType = InterfaceType {
+        scalar: ScalarType::Float { width: 32 },
+        components: 4,
+    };
+    const UINT: InterfaceType = InterfaceType {
+        scalar: ScalarType::Int {
+            width: 32,
+            signed: false,
+        },
+        components: 1,
+    };
+
+    fn variable(name: &str, location: u32, ty: InterfaceType) -> InterfaceVariable {
+        InterfaceVariable {
+            name: name.to_string(),
+            location,
+            component: 0,
+            ty,
+            interpolation: Interpolation::default(),
+            patch: false,
+            per_primitive: false,
+        }
+    }
+
+    fn flat(variable: InterfaceVariable) -> InterfaceVariable {
+        InterfaceVariable {
+            interpolation: Interpolation {
+                flat: true,
+                ..variable.interpolation
+            },
+            ..variable
+        }
+    }
+
+    fn entry_point(
+        stage: vk::ShaderStageFlags, inputs: Vec<InterfaceVariable>,
+        outputs: Vec<InterfaceVariable>,
+    ) -> ShaderEntryPoint {
+        ShaderEntryPoint {
+            name: "main".to_string(),
+            stage,
+            interface: StageInterface { inputs, outputs },
+        }
+    }
+
+    fn ident(name: &str) -> Ident {
+        Ident::new(name, Span::call_site())
+    }
+
+    fn check(producer: &[InterfaceVariable], consumer: &[InterfaceVariable]) -> Result<(), String> {
+        let producer = StageInterface {
+            outputs: producer.to_vec(),
+            ..Default::default()
+        };
+        let consumer = StageInterface {
+            inputs: consumer.to_vec(),
+            ..Default::default()
+        };
+        match_interfaces(&ident("Vert"), &producer, &ident("Frag"), &consumer)
+    }
+
+    #[test]
+    fn matching_interfaces() {
+        let outputs = [variable("color", 0, VEC4), flat(variable("id", 1, UINT))];
+        assert_eq!(check(&outputs, &outputs), Ok(()));
+        // Outputs nobody reads are fine
+        assert_eq!(check(&outputs, &outputs[..1]), Ok(()));
+        // The consumer may read fewer components
+        let float = InterfaceType {
+            components: 1,
+            ..VEC4
+        };
+        assert_eq!(check(&outputs, &[variable("color", 0, float)]), Ok(()));
+    }
+
+    #[test]
+    fn mismatched_interfaces() {
+        let outputs = [variable("color", 0, VEC4)];
+
+        assert_eq!(
+            check(&outputs, &[variable("normal", 1, VEC4)]),
+            Err("`Frag` reads `normal` at location 1 which `Vert` doesn't write".to_string())
+        );
+        assert_eq!(
+            check(&outputs, &[flat(variable("color", 0, UINT))]),
+            Err(
+                "`Frag` reads `color` at location 0 as uint but `Vert` writes `color` at \
+                 location 0 as vec4"
+                    .to_string()
+            )
+        );
+
+        let mut per_primitive = variable("color", 0, VEC4);
+        per_primitive.per_primitive = true;
+        assert_eq!(
+            check(&outputs, &[per_primitive]),
+            Err(
+                "`Frag` reads `color` at location 0 per-primitive but `Vert` writes it per-vertex"
+                    .to_string()
+            )
+        );
+
+        let mut sample = variable("color", 0, VEC4);
+        sample.interpolation.sample = true;
+        let mut centroid = variable("color", 0, VEC4);
+        centroid.interpolation.centroid = true;
+        assert!(check(&[sample], &[centroid]).is_err());
+    }
+
+    #[test]
+    fn fragment_inputs_of_integers_are_flat() {
+        let interface = |inputs| StageInterface {
+            inputs,
+            ..Default::default()
+        };
+
+        assert!(check_fragment_inputs(&interface(vec![variable("id", 0, UINT)])).is_err());
+        assert_eq!(
+            check_fragment_inputs(&interface(vec![flat(variable("id", 0, UINT))])),
+            Ok(())
+        );
+
+        let mut per_primitive = variable("id", 0, UINT);
+        per_primitive.per_primitive = true;
+        assert_eq!(
+            check_fragment_inputs(&interface(vec![per_primitive])),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn stages_are_chained_within_pipeline_layouts() {
+        let vertex = entry_point(vk::ShaderStageFlags::VERTEX, vec![], vec![variable(
+            "color", 0, VEC4,
+        )]);
+        let mesh = entry_point(vk::ShaderStageFlags::MESH_EXT, vec![], vec![variable(
+            "color", 0, VEC4,
+        )]);
+        let fragment = entry_point(
+            vk::ShaderStageFlags::FRAGMENT,
+            vec![variable("normal", 1, VEC4)],
+            vec![],
+        );
+        let (vert, task_mesh, frag) = (ident("Vert"), ident("Mesh"), ident("Frag"));
+
+        // Not part of a pipeline layout
+        let shaders = [(&vert, &vertex), (&frag, &fragment)];
+        assert_eq!(validate_stage_interfaces(&shaders, &[]).len(), 0);
+
+        let pipeline = shaders.to_vec();
+        assert_eq!(
+            validate_stage_interfaces(&shaders, std::slice::from_ref(&pipeline)).len(),
+            1
+        );
+        // Reported once for layouts sharing the shaders
+        assert_eq!(
+            validate_stage_interfaces(&shaders, &[pipeline.clone(), pipeline]).len(),
+            1
+        );
+
+        // Fragment shaders read the outputs of mesh shaders
+        let pipeline = vec![(&task_mesh, &mesh), (&frag, &fragment)];
+        assert_eq!(validate_stage_interfaces(&[], &[pipeline]).len(), 1);
+
+        // Several shaders of a stage aren't a single pipeline
+        let other_vert = ident("OtherVert");
+        let pipeline = vec![(&vert, &vertex), (&other_vert, &vertex), (&frag, &fragment)];
+        assert_eq!(validate_stage_interfaces(&[], &[pipeline]).len(), 0);
+    }
+
+    #[test]
+    fn tessellation_needs_both_stages() {
+        let control = entry_point(vk::ShaderStageFlags::TESSELLATION_CONTROL, vec![], vec![]);
+        let evaluation = entry_point(
+            vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+            vec![],
+            vec![],
+        );
+        let (tesc, tese) = (ident("Tesc"), ident("Tese"));
+
+        assert_eq!(
+            validate_stage_interfaces(&[], &[vec![(&tesc, &control)]]).len(),
+            1
+        );
+        assert_eq!(
+            validate_stage_interfaces(&[], &[vec![(&tese, &evaluation)]]).len(),
+            1
+        );
+        assert_eq!(
+            validate_stage_interfaces(&[], &[vec![(&tesc, &control), (&tese, &evaluation)]]).len(),
+            0
+        );
+    }
+}
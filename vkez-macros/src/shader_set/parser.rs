@@ -1,14 +1,74 @@
 use std::ops::BitOr;
 
 use proc_macro_error::emit_error;
-use structmeta::StructMeta;
-use syn::{spanned::Spanned, visit_mut::VisitMut, Ident, LitStr, Path};
+use structmeta::{NameArgs, StructMeta};
+use syn::{
+    parse::Parse, spanned::Spanned, visit_mut::VisitMut, Attribute, Ident, LitBool, LitInt, LitStr,
+    Meta, Path,
+};
+
+use crate::{
+    compiler::{HlslRegisterShift, ShaderCompileOptions, ShaderDefine},
+    sampler::NamedSamplerArgs,
+};
+
+#[derive(StructMeta, Default, Debug)]
+pub(crate) struct ShaderSetArgs {
+    pub relative_to: Option<LitStr>,
+    pub include_dirs: Option<Vec<LitStr>>,
+    pub target: Option<LitStr>,
+    pub spirv: Option<LitStr>,
+    pub optimize: Option<LitStr>,
+    pub debug_info: Option<LitBool>,
+    pub auto_bind: Option<LitBool>,
+    pub register_shift: Option<NameArgs<HlslRegisterShift>>,
+}
+
+impl ShaderSetArgs {
+    pub fn compile_options(&self) -> ShaderCompileOptions {
+        ShaderCompileOptions {
+            lang: None,
+            target: self.target.clone(),
+            spirv: self.spirv.clone(),
+            optimize: self.optimize.clone(),
+            debug_info: self.debug_info.clone(),
+            auto_bind: self.auto_bind.clone(),
+            register_shift: self.register_shift.clone(),
+        }
+    }
+}
 
 #[derive(StructMeta, Default, Debug)]
 pub(crate) struct ShaderSetShaderAttributes {
     pub file: Option<LitStr>,
+    pub relative_to: Option<LitStr>,
     pub lang: Option<LitStr>,
     pub kind: Option<Ident>,
+    /// Defaults to every entry point of the module, the declared struct is then
+    /// replaced by one per entry point like `MyShaderVSMain`.
+    pub entry: Option<LitStr>,
+    pub include_dirs: Option<Vec<LitStr>>,
+    pub defines: Option<Vec<ShaderDefine>>,
+    pub target: Option<LitStr>,
+    pub spirv: Option<LitStr>,
+    pub optimize: Option<LitStr>,
+    pub debug_info: Option<LitBool>,
+    pub auto_bind: Option<LitBool>,
+    pub register_shift: Option<NameArgs<HlslRegisterShift>>,
+}
+
+impl ShaderSetShaderAttributes {
+    pub fn compile_options(&self) -> ShaderCompileOptions {
+        ShaderCompileOptions {
+            lang: self.lang.clone(),
+            target: self.target.clone(),
+            spirv: self.spirv.clone(),
+            optimize: self.optimize.clone(),
+            debug_info: self.debug_info.clone(),
+            auto_bind: self.auto_bind.clone(),
+            register_shift: self.register_shift.clone(),
+        }
+    }
 }
 
 impl BitOr for ShaderSetShaderAttributes {
@@ -17,15 +77,42 @@ impl BitOr for ShaderSetShaderAttributes {
         // FIXME: handle both sides having a value ?
         Self {
             file: self.file.or(rhs.file),
+            relative_to: self.relative_to.or(rhs.relative_to),
             lang: self.lang.or(rhs.lang),
             kind: self.kind.or(rhs.kind),
+            entry: self.entry.or(rhs.entry),
+            include_dirs: self.include_dirs.or(rhs.include_dirs),
+            defines: self.defines.or(rhs.defines),
+            target: self.target.or(rhs.target),
+            spirv: self.spirv.or(rhs.spirv),
+            optimize: self.optimize.or(rhs.optimize),
+            debug_info: self.debug_info.or(rhs.debug_info),
+            auto_bind: self.auto_bind.or(rhs.auto_bind),
+            register_shift: self.register_shift.or(rhs.register_shift),
         }
     }
 }
 
+/// `variable_count(binding = 3, max = 1024)`
+#[derive(StructMeta, Debug, Clone)]
+pub(crate) struct VariableCount {
+    pub binding: LitInt,
+    /// Required for runtime-sized arrays, defaults to the size of the array.
+    pub max: Option<LitInt>,
+}
+
 #[derive(StructMeta, Default, Debug)]
 pub(crate) struct ShaderSetDescriptorSetAttributes {
     pub from_shader: Option<Path>,
+    pub set: Option<LitInt>,
+    /// Bindings that may be left unwritten, like `partially_bound(0, 2)`.
+    pub partially_bound: Option<Vec<LitInt>>,
+    pub update_after_bind: Option<Vec<LitInt>>,
+    pub update_unused_while_pending: Option<Vec<LitInt>>,
+    /// Only for the last binding, the count is chosen when allocating.
+    pub variable_count: Option<NameArgs<VariableCount>>,
+    /// `immutable_samplers(shadow_map(compare_op = LESS), ...)`
+    pub immutable_samplers: Option<Vec<NamedSamplerArgs>>,
 }
 
 impl BitOr for ShaderSetDescriptorSetAttributes {
@@ -34,6 +121,30 @@ impl BitOr for ShaderSetDescriptorSetAttributes {
         // FIXME: handle both sides having a value ?
         Self {
             from_shader: self.from_shader.or(rhs.from_shader),
+            set: self.set.or(rhs.set),
+            partially_bound: self.partially_bound.or(rhs.partially_bound),
+            update_after_bind: self.update_after_bind.or(rhs.update_after_bind),
+            update_unused_while_pending: self
+                .update_unused_while_pending
+                .or(rhs.update_unused_while_pending),
+            variable_count: self.variable_count.or(rhs.variable_count),
+            immutable_samplers: self.immutable_samplers.or(rhs.immutable_samplers),
+        }
+    }
+}
+
+#[derive(StructMeta, Default, Debug)]
+pub(crate) struct ShaderSetPipelineLayoutAttributes {
+    /// Defaults to every shader of the set. With at most one shader per stage,
+    /// the outputs of each stage are checked against the inputs of the next.
+    pub shaders: Option<Vec<Path>>,
+}
+
+impl BitOr for ShaderSetPipelineLayoutAttributes {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self {
+            shaders: self.shaders.or(rhs.shaders),
         }
     }
 }
@@ -42,12 +153,14 @@ impl BitOr for ShaderSetDescriptorSetAttributes {
 pub(crate) struct AccumulateShaderItemsVisitor {
     pub shaders: Vec<(Ident, ShaderSetShaderAttributes)>,
     pub descriptor_sets: Vec<(Ident, ShaderSetDescriptorSetAttributes)>,
+    pub pipeline_layouts: Vec<(Ident, ShaderSetPipelineLayoutAttributes)>,
 }
 
 impl VisitMut for AccumulateShaderItemsVisitor {
     fn visit_item_struct_mut(&mut self, item: &mut syn::ItemStruct) {
         let mut shader_attrs = Vec::new();
         let mut descriptor_set_attrs = Vec::new();
+        let mut pipeline_layout_attrs = Vec::new();
 
         // Collect interresting attributes
         // Iterate attributes in reverse order so that when we remove some items, the
@@ -60,6 +173,7 @@ impl VisitMut for AccumulateShaderItemsVisitor {
             let to_container = match path {
                 _ if path.is_ident("shader") => Some(&mut shader_attrs),
                 _ if path.is_ident("descriptor_set") => Some(&mut descriptor_set_attrs),
+                _ if path.is_ident("pipeline_layout") => Some(&mut pipeline_layout_attrs),
                 _ => None,
             };
 
@@ -70,36 +184,136 @@ impl VisitMut for AccumulateShaderItemsVisitor {
         }
 
         // Check for too many attributes
-        let has_shader_attrs = !shader_attrs.is_empty();
-        let has_descriptor_set_attrs = !descriptor_set_attrs.is_empty();
+        let kinds = [
+            ("shader", shader_attrs.first()),
+            ("descriptor set", descriptor_set_attrs.first()),
+            ("pipeline layout", pipeline_layout_attrs.first()),
+        ];
+        let found = kinds
+            .iter()
+            .filter_map(|(kind, attr)| attr.map(|attr| (kind, attr)))
+            .collect::<Vec<_>>();
 
-        if has_shader_attrs && has_descriptor_set_attrs {
-            let first_shader_attr = shader_attrs.first().unwrap();
-            let first_descriptor_set_attr = descriptor_set_attrs.first().unwrap();
+        if let [(first_kind, first_attr), (second_kind, second_attr), ..] = found.as_slice() {
             emit_error!(
                 item.span(), "Item has mutually exclusive attributes";
-                help = first_shader_attr.span() => "Found shader only attribute";
-                help = first_descriptor_set_attr.span() => "Found descriptor set only attribute";
+                help = first_attr.span() => "Found {} only attribute", first_kind;
+                help = second_attr.span() => "Found {} only attribute", second_kind;
             );
         }
 
+        let has_shader_attrs = !shader_attrs.is_empty();
+        let has_descriptor_set_attrs = !descriptor_set_attrs.is_empty();
+        let has_pipeline_layout_attrs = !pipeline_layout_attrs.is_empty();
+
         if has_shader_attrs {
-            self.shaders.push((
-                item.ident.clone(),
-                shader_attrs
-                    .into_iter()
-                    .fold(ShaderSetShaderAttributes::default(), |attributes, attr| {
-                        attributes | attr.parse_args().unwrap()
-                    }),
-            ));
+            if let Some(attributes) = parse_attributes(shader_attrs) {
+                self.shaders.push((item.ident.clone(), attributes));
+            }
         } else if has_descriptor_set_attrs {
-            self.descriptor_sets.push((
-                item.ident.clone(),
-                descriptor_set_attrs.into_iter().fold(
-                    ShaderSetDescriptorSetAttributes::default(),
-                    |attribtes, attr| attribtes | attr.parse_args().unwrap(),
-                ),
-            ));
+            if let Some(attributes) = parse_attributes(descriptor_set_attrs) {
+                self.descriptor_sets.push((item.ident.clone(), attributes));
+            }
+        } else if has_pipeline_layout_attrs {
+            // `#[pipeline_layout]` without arguments
+            pipeline_layout_attrs.retain(|attr| !matches!(attr.meta, Meta::Path(_)));
+            if let Some(attributes) = parse_attributes(pipeline_layout_attrs) {
+                self.pipeline_layouts.push((item.ident.clone(), attributes));
+            }
+        }
+    }
+}
+
+/// Merges the arguments of the attributes. Errors are emitted and the item is
+/// skipped, so that the other items are still checked.
+fn parse_attributes<T>(attrs: Vec<Attribute>) -> Option<T>
+where
+    T: Parse + Default + BitOr<Output = T>,
+{
+    merge_attributes(&attrs)
+        .map_err(|errors| {
+            for (attr, e) in errors {
+                emit_error!(attr, e);
+            }
+        })
+        .ok()
+}
+
+/// Every attribute that fails to parse, with its error.
+fn merge_attributes<T>(attrs: &[Attribute]) -> Result<T, Vec<(&Attribute, syn::Error)>>
+where
+    T: Parse + Default + BitOr<Output = T>,
+{
+    let mut merged = T::default();
+    let mut errors = Vec::new();
+    for attr in attrs {
+        match attr.parse_args::<T>() {
+            Ok(attributes) => merged = merged | attributes,
+            Err(e) => errors.push((attr, e)),
         }
     }
+
+    if errors.is_empty() {
+        Ok(merged)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    fn merge<T>(attrs: &[Attribute]) -> Result<T, Vec<String>>
+    where
+        T: Parse + Default + BitOr<Output = T>,
+    {
+        merge_attributes(attrs)
+            .map_err(|errors| errors.into_iter().map(|(_, e)| e.to_string()).collect())
+    }
+
+    #[test]
+    fn merged_attributes() {
+        let attrs: Vec<Attribute> = vec![
+            parse_quote!(#[shader(file = "shader.vert")]),
+            parse_quote!(#[shader(kind = vertex, entry = "main")]),
+        ];
+        let shader = merge::<ShaderSetShaderAttributes>(&attrs).unwrap();
+        assert_eq!(shader.file.unwrap().value(), "shader.vert");
+        assert_eq!(shader.kind.unwrap(), "vertex");
+        assert_eq!(shader.entry.unwrap().value(), "main");
+
+        let attrs: Vec<Attribute> = vec![parse_quote!(
+            #[descriptor_set(from_shader = Vert, set = 1, partially_bound(0, 2))]
+        )];
+        let set = merge::<ShaderSetDescriptorSetAttributes>(&attrs).unwrap();
+        assert!(set.from_shader.unwrap().is_ident("Vert"));
+        assert_eq!(set.set.unwrap().base10_parse::<u32>().unwrap(), 1);
+        assert_eq!(set.partially_bound.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn malformed_attributes() {
+        // Every malformed attribute is reported
+        let attrs: Vec<Attribute> = vec![
+            parse_quote!(#[shader(file = 3)]),
+            parse_quote!(#[shader(kind = vertex)]),
+            parse_quote!(#[shader(unknown = "value")]),
+        ];
+        let errors = merge::<ShaderSetShaderAttributes>(&attrs).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0], "expected string literal");
+
+        let attrs: Vec<Attribute> = vec![parse_quote!(#[descriptor_set(set = "1")])];
+        assert!(merge::<ShaderSetDescriptorSetAttributes>(&attrs).is_err());
+
+        let attrs: Vec<Attribute> = vec![parse_quote!(#[pipeline_layout(shaders = Vert)])];
+        assert!(merge::<ShaderSetPipelineLayoutAttributes>(&attrs).is_err());
+
+        // Arguments are required
+        let attrs: Vec<Attribute> = vec![parse_quote!(#[shader])];
+        assert!(merge::<ShaderSetShaderAttributes>(&attrs).is_err());
+    }
 }
@@ -0,0 +1,170 @@
+use std::fmt::Debug;
+
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{Ident, LitByteStr};
+use vkez_core::ash::vk;
+
+const SHADER_STAGES: &[(vk::ShaderStageFlags, &str)] = &[
+    (vk::ShaderStageFlags::VERTEX, "VERTEX"),
+    (
+        vk::ShaderStageFlags::TESSELLATION_CONTROL,
+        "TESSELLATION_CONTROL",
+    ),
+    (
+        vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        "TESSELLATION_EVALUATION",
+    ),
+    (vk::ShaderStageFlags::GEOMETRY, "GEOMETRY"),
+    (vk::ShaderStageFlags::FRAGMENT, "FRAGMENT"),
+    (vk::ShaderStageFlags::COMPUTE, "COMPUTE"),
+    (vk::ShaderStageFlags::RAYGEN_KHR, "RAYGEN_KHR"),
+    (vk::ShaderStageFlags::ANY_HIT_KHR, "ANY_HIT_KHR"),
+    (vk::ShaderStageFlags::CLOSEST_HIT_KHR, "CLOSEST_HIT_KHR"),
+    (vk::ShaderStageFlags::MISS_KHR, "MISS_KHR"),
+    (vk::ShaderStageFlags::INTERSECTION_KHR, "INTERSECTION_KHR"),
+    (vk::ShaderStageFlags::CALLABLE_KHR, "CALLABLE_KHR"),
+    (vk::ShaderStageFlags::TASK_EXT, "TASK_EXT"),
+    (vk::ShaderStageFlags::MESH_EXT, "MESH_EXT"),
+];
+
+/// `vk::ShaderStageFlags` variant from its name.
+pub(crate) fn parse_stage(name: &str) -> Option<vk::ShaderStageFlags> {
+    match name {
+        "ALL" => Some(vk::ShaderStageFlags::ALL),
+        "ALL_GRAPHICS" => Some(vk::ShaderStageFlags::ALL_GRAPHICS),
+        _ => SHADER_STAGES
+            .iter()
+            .find(|(_, stage_name)| *stage_name == name)
+            .map(|(stage, _)| *stage),
+    }
+}
+
+pub(crate) fn stage_flags_tokens(stages: vk::ShaderStageFlags) -> TokenStream2 {
+    if stages == vk::ShaderStageFlags::ALL {
+        return quote!(::vkez_core::ash::vk::ShaderStageFlags::ALL);
+    }
+    if stages == vk::ShaderStageFlags::ALL_GRAPHICS {
+        return quote!(::vkez_core::ash::vk::ShaderStageFlags::ALL_GRAPHICS);
+    }
+
+    let names = SHADER_STAGES
+        .iter()
+        .filter(|(stage, _)| stages.contains(*stage))
+        .map(|(_, name)| Ident::new(name, Span::call_site()))
+        .collect::<Vec<_>>();
+
+    match names.as_slice() {
+        [] => quote!(::vkez_core::ash::vk::ShaderStageFlags::empty()),
+        [name] => quote!(::vkez_core::ash::vk::ShaderStageFlags::#name),
+        names => quote! {
+            ::vkez_core::ash::vk::ShaderStageFlags::from_raw(
+                #(::vkez_core::ash::vk::ShaderStageFlags::#names.as_raw())|*
+            )
+        },
+    }
+}
+
+pub(crate) fn binding_flags_tokens(flags: vk::DescriptorBindingFlags) -> TokenStream2 {
+    let names = [
+        (
+            vk::DescriptorBindingFlags::UPDATE_AFTER_BIND,
+            "UPDATE_AFTER_BIND",
+        ),
+        (
+            vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING,
+            "UPDATE_UNUSED_WHILE_PENDING",
+        ),
+        (
+            vk::DescriptorBindingFlags::PARTIALLY_BOUND,
+            "PARTIALLY_BOUND",
+        ),
+        (
+            vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT,
+            "VARIABLE_DESCRIPTOR_COUNT",
+        ),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| flags.contains(*flag))
+    .map(|(_, name)| Ident::new(name, Span::call_site()))
+    .collect::<Vec<_>>();
+
+    match names.as_slice() {
+        [] => quote!(::vkez_core::ash::vk::DescriptorBindingFlags::empty()),
+        [name] => quote!(::vkez_core::ash::vk::DescriptorBindingFlags::#name),
+        names => quote! {
+            ::vkez_core::ash::vk::DescriptorBindingFlags::from_raw(
+                #(::vkez_core::ash::vk::DescriptorBindingFlags::#names.as_raw())|*
+            )
+        },
+    }
+}
+
+pub(crate) fn descriptor_type_tokens(ty: vk::DescriptorType) -> TokenStream2 {
+    let name = Ident::new(&format!("{ty:?}"), Span::call_site());
+    quote!(::vkez_core::ash::vk::DescriptorType::#name)
+}
+
+/// `::vkez_core::ash::vk::#ty::#value` from the debug name of the variant.
+pub(crate) fn variant_tokens(ty: &str, value: impl Debug) -> TokenStream2 {
+    let ty = Ident::new(ty, Span::call_site());
+    let name = Ident::new(&format!("{value:?}"), Span::call_site());
+    quote!(::vkez_core::ash::vk::#ty::#name)
+}
+
+pub(crate) fn format_tokens(format: vk::Format) -> TokenStream2 {
+    let name = Ident::new(&format!("{format:?}"), Span::call_site());
+    quote!(::vkez_core::ash::vk::Format::#name)
+}
+
+/// `&'static CStr` constant expression.
+pub(crate) fn cstr_tokens(value: &str, span: Span) -> TokenStream2 {
+    let mut bytes = value.as_bytes().to_vec();
+    bytes.push(0);
+    let bytes = LitByteStr::new(&bytes, span);
+    quote!(unsafe { ::std::ffi::CStr::from_bytes_with_nul_unchecked(#bytes) })
+}
+
+/// `MyComputeShader` to `my_compute_shader`, `HDRShader` to `hdr_shader`.
+pub(crate) fn snake_case_ident(ident: &Ident) -> Ident {
+    let chars = ident.to_string().chars().collect::<Vec<_>>();
+
+    let mut snake_case = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let previous = chars[i - 1];
+            let next = chars.get(i + 1);
+            if !previous.is_uppercase() && previous != '_'
+                || previous.is_uppercase() && next.is_some_and(|next| next.is_lowercase())
+            {
+                snake_case.push('_');
+            }
+        }
+        snake_case.extend(c.to_lowercase());
+    }
+
+    Ident::new(&snake_case, ident.span())
+}
+
+/// `VSMain` as is, `cs_main` to `CsMain`, for the items generated per entry
+/// point.
+pub(crate) fn pascal_case_ident(name: &str, span: Span) -> Result<Ident, String> {
+    let mut pascal_case = String::new();
+    for word in name.split('_') {
+        let mut chars = word.chars();
+        pascal_case.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+        pascal_case.extend(chars);
+    }
+
+    if pascal_case.is_empty()
+        || pascal_case.starts_with(|c: char| c.is_ascii_digit())
+        || !pascal_case.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        return Err(format!(
+            "Entry point `{name}` can't be turned into a Rust identifier, select one with \
+             `entry = \"...\"`"
+        ));
+    }
+
+    Ok(Ident::new(&pascal_case, span))
+}
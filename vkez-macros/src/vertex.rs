@@ -0,0 +1,158 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use structmeta::StructMeta;
+use syn::{Data, DeriveInput, Fields, Ident, LitInt, LitStr, Path};
+
+use crate::{reflect::VertexInput, tokens::format_tokens};
+
+#[derive(StructMeta, Default)]
+struct VertexArgs {
+    /// Vertex shader the fields are checked against.
+    shader: Option<Path>,
+    /// Per instance rather than per vertex.
+    instance: bool,
+}
+
+#[derive(StructMeta, Default)]
+struct VertexFieldArgs {
+    location: Option<LitInt>,
+    /// `vk::Format` variant, defaults to the `VertexFormat` of the field type.
+    /// Normalized and scaled formats can only be given this way.
+    format: Option<Ident>,
+}
+
+pub(crate) fn derive_vertex_impl(item: DeriveInput) -> Result<TokenStream2, TokenStream2> {
+    let ident = &item.ident;
+
+    let Data::Struct(data) = &item.data else {
+        return Err(error(ident, "Vertex can only be derived for structs"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(error(
+            ident,
+            "Vertex can only be derived for structs with named fields",
+        ));
+    };
+    if !item.generics.params.is_empty() {
+        return Err(error(
+            &item.generics,
+            "Generic vertex types are not supported",
+        ));
+    }
+
+    let args = parse_attribute::<VertexArgs>(&item.attrs)?;
+
+    let mut vertex_fields = Vec::new();
+    for field in &fields.named {
+        let field_args = parse_attribute::<VertexFieldArgs>(&field.attrs)?;
+        let location = match field_args.location.as_ref() {
+            Some(explicit) => {
+                let location = explicit
+                    .base10_parse::<u32>()
+                    .map_err(|e| e.to_compile_error())?;
+                quote!(::core::option::Option::Some(#location))
+            }
+            None => quote!(::core::option::Option::None),
+        };
+
+        let field_ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        // An explicit format is a single attribute
+        let (format, attributes) = match field_args.format.as_ref() {
+            Some(format) => (quote!(::vkez_core::ash::vk::Format::#format), quote!(1)),
+            None => (
+                quote!(<#ty as ::vkez_core::vertex::VertexFormat>::FORMAT),
+                quote!(<#ty as ::vkez_core::vertex::VertexFormat>::ATTRIBUTES),
+            ),
+        };
+
+        vertex_fields.push(quote! {
+            ::vkez_core::vertex::VertexField {
+                location: #location,
+                format: #format,
+                offset: ::core::mem::offset_of!(#ident, #field_ident) as u32,
+                attributes: #attributes,
+                stride: (::core::mem::size_of::<#ty>() as u32) / #attributes,
+            }
+        });
+    }
+
+    let input_rate = if args.instance {
+        quote!(::vkez_core::ash::vk::VertexInputRate::INSTANCE)
+    } else {
+        quote!(::vkez_core::ash::vk::VertexInputRate::VERTEX)
+    };
+
+    let check = args.shader.as_ref().map(|shader| {
+        let message = LitStr::new(
+            &format!(
+                "The fields of `{}` don't match the inputs of `{}`",
+                ident,
+                quote!(#shader).to_string().replace(' ', "")
+            ),
+            ident.span(),
+        );
+
+        quote! {
+            const _: () = ::core::assert!(
+                ::vkez_core::vertex::attributes_match_inputs(
+                    <#shader as ::vkez_core::vertex::VertexShader>::INPUTS,
+                    <#ident as ::vkez_core::vertex::Vertex>::ATTRIBUTES,
+                ),
+                #message
+            );
+        }
+    });
+
+    Ok(quote! {
+        unsafe impl ::vkez_core::vertex::Vertex for #ident {
+            const ATTRIBUTES: &'static [::vkez_core::ash::vk::VertexInputAttributeDescription] = {
+                const FIELDS: &[::vkez_core::vertex::VertexField] = &[#(#vertex_fields),*];
+                const ATTRIBUTES: [
+                    ::vkez_core::ash::vk::VertexInputAttributeDescription;
+                    ::vkez_core::vertex::vertex_attribute_count(FIELDS)
+                ] = ::vkez_core::vertex::vertex_attributes(FIELDS);
+                &ATTRIBUTES
+            };
+            const INPUT_RATE: ::vkez_core::ash::vk::VertexInputRate = #input_rate;
+        }
+
+        #check
+    })
+}
+
+/// Arguments of the `#[vertex(...)]` attribute, if any.
+fn parse_attribute<T: syn::parse::Parse + Default>(
+    attrs: &[syn::Attribute],
+) -> Result<T, TokenStream2> {
+    let mut vertex_attrs = attrs.iter().filter(|attr| attr.path().is_ident("vertex"));
+
+    let Some(attr) = vertex_attrs.next() else {
+        return Ok(T::default());
+    };
+    if let Some(duplicate) = vertex_attrs.next() {
+        return Err(error(duplicate, "Duplicate `vertex` attribute"));
+    }
+
+    attr.parse_args().map_err(|e| e.to_compile_error())
+}
+
+fn error(tokens: impl quote::ToTokens, message: &str) -> TokenStream2 {
+    syn::Error::new_spanned(tokens, message).to_compile_error()
+}
+
+/// `&[VertexAttribute]` of the reflected vertex inputs.
+pub(crate) fn vertex_inputs_tokens(inputs: &[VertexInput]) -> TokenStream2 {
+    let inputs = inputs.iter().map(|input| {
+        let location = input.location;
+        let format = format_tokens(input.format);
+        quote! {
+            ::vkez_core::vertex::VertexAttribute {
+                location: #location,
+                format: #format,
+            }
+        }
+    });
+
+    quote!(&[#(#inputs),*])
+}
@@ -8,82 +8,25 @@ use vkez::{
     bootstrap::{AshDeviceExt, AshInstanceExt, PhysicalDeviceCriteria, QueueFamilyRequest},
     tracing, vk_mem,
 };
-use vkez_core::{descriptor_sets::RawDescriptorSetInfo, shaders::RawShaderInfo};
+use vkez_core::{
+    descriptor_sets::RawDescriptorSetInfo,
+    layout::RuntimeArrayBlock,
+    pipeline_layout::RawPipelineLayoutInfo,
+    shaders::{ComputeShader, RawShaderInfo},
+};
 
+#[vkez_macros::shader_set]
 pub mod my_shader_set {
-    use std::ffi::CStr;
-
-    use vkez::ash::{vk, vk::TaggedStructure};
-    use vkez_core::{descriptor_sets::RawDescriptorSetInfo, shaders::RawShaderInfo};
-
+    #[shader(file = "../examples/add.comp.glsl", kind = Compute)]
     pub struct MyComputeShader;
 
-    unsafe impl RawShaderInfo for MyComputeShader {
-        const VIBE_CHECK: &'static str = "";
-        const CODE: &'static [u32] = &super::compute_shader_module::CODE;
-        const STAGE: vk::ShaderStageFlags = vk::ShaderStageFlags::COMPUTE;
-
-        fn entry_point() -> &'static std::ffi::CStr {
-            const NAME: &[u8] = b"main\0";
-            unsafe { CStr::from_ptr(NAME.as_ptr() as *const _) }
-        }
-    }
-
+    #[descriptor_set(from_shader = MyComputeShader)]
     pub struct MyDescriptorSet;
 
-    unsafe impl RawDescriptorSetInfo for MyDescriptorSet {
-        const LAYOUT_BINDINGS_CREATE_INFO: &'static [vk::DescriptorSetLayoutBinding] = &[
-            vk::DescriptorSetLayoutBinding {
-                binding: 0,
-                descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::COMPUTE,
-                p_immutable_samplers: std::ptr::null(),
-            },
-            vk::DescriptorSetLayoutBinding {
-                binding: 1,
-                descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::COMPUTE,
-                p_immutable_samplers: std::ptr::null(),
-            },
-            vk::DescriptorSetLayoutBinding {
-                binding: 2,
-                descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::COMPUTE,
-                p_immutable_samplers: std::ptr::null(),
-            },
-        ];
-
-        const LAYOUT_CREATE_INFO: vk::DescriptorSetLayoutCreateInfo =
-            vk::DescriptorSetLayoutCreateInfo {
-                s_type: vk::DescriptorSetLayoutCreateInfo::STRUCTURE_TYPE,
-                p_next: std::ptr::null_mut(),
-                flags: vk::DescriptorSetLayoutCreateFlags::empty(),
-                binding_count: Self::LAYOUT_BINDINGS_CREATE_INFO.len() as _,
-                p_bindings: Self::LAYOUT_BINDINGS_CREATE_INFO.as_ptr(),
-            };
-
-        const POOL_SIZES_FOR_ONE: &'static [vk::DescriptorPoolSize] = &[vk::DescriptorPoolSize {
-            ty: vk::DescriptorType::STORAGE_BUFFER,
-            descriptor_count: 3,
-        }];
-    }
+    #[pipeline_layout]
+    pub struct MyPipelineLayout;
 }
 
-// #[vkez_macros::shader_set]
-// pub mod my_shader_set {
-//     #[shader(file = "./examples/add.comp.glsl", kind = Compute)]
-//     pub struct MyComputeShader;
-
-//     #[descriptor_set(from_shader = MyComputeShader)]
-//     pub struct MyDescriptorSet;
-// }
-
-#[vkez_macros::shader_module("./examples/add.comp.glsl", kind = "Compute")]
-pub mod compute_shader_module {}
-
 fn main() -> eyre::Result<()> {
     tracing_subscriber::fmt()
         .with_max_level(Level::DEBUG)
@@ -143,7 +86,7 @@ fn main() -> eyre::Result<()> {
     let mut buffer_a = unsafe {
         allocator.create_buffer(
             &vk::BufferCreateInfo::builder()
-                .size(256 * mem::size_of::<f32>() as vk::DeviceSize)
+                .size(my_shader_set::my_compute_shader::A::buffer_size(256))
                 .usage(vk::BufferUsageFlags::STORAGE_BUFFER)
                 .sharing_mode(vk::SharingMode::EXCLUSIVE),
             &vk_mem::AllocationCreateInfo {
@@ -157,7 +100,7 @@ fn main() -> eyre::Result<()> {
     let mut buffer_b = unsafe {
         allocator.create_buffer(
             &vk::BufferCreateInfo::builder()
-                .size(256 * mem::size_of::<f32>() as vk::DeviceSize)
+                .size(my_shader_set::my_compute_shader::B::buffer_size(256))
                 .usage(vk::BufferUsageFlags::STORAGE_BUFFER)
                 .sharing_mode(vk::SharingMode::EXCLUSIVE),
             &vk_mem::AllocationCreateInfo {
@@ -171,7 +114,7 @@ fn main() -> eyre::Result<()> {
     let mut buffer_c = unsafe {
         allocator.create_buffer(
             &vk::BufferCreateInfo::builder()
-                .size(256 * mem::size_of::<f32>() as vk::DeviceSize)
+                .size(my_shader_set::my_compute_shader::C::buffer_size(256))
                 .usage(vk::BufferUsageFlags::STORAGE_BUFFER)
                 .sharing_mode(vk::SharingMode::EXCLUSIVE),
             &vk_mem::AllocationCreateInfo {
@@ -205,66 +148,27 @@ fn main() -> eyre::Result<()> {
     let descriptor_pool =
         unsafe { my_shader_set::MyDescriptorSet::create_pool_for_set(&device, 1)? };
 
-    let descriptor_set_layout = unsafe { my_shader_set::MyDescriptorSet::create_layout(&device)? };
+    let (compute_pipeline_layout, descriptor_set_layouts) =
+        unsafe { my_shader_set::MyPipelineLayout::create_pipeline_layout(&device)? };
 
     let descriptor_set = unsafe {
         my_shader_set::MyDescriptorSet::allocate_one_set(
             &device,
             descriptor_pool,
-            descriptor_set_layout,
+            descriptor_set_layouts[0],
         )?
     };
 
     unsafe {
-        device.update_descriptor_sets(
-            &[
-                vk::WriteDescriptorSet::builder()
-                    .dst_set(descriptor_set)
-                    .dst_binding(0)
-                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-                    .buffer_info(from_ref(
-                        &vk::DescriptorBufferInfo::builder()
-                            .buffer(buffer_a.0)
-                            .offset(0)
-                            .range(vk::WHOLE_SIZE),
-                    ))
-                    .build(),
-                vk::WriteDescriptorSet::builder()
-                    .dst_set(descriptor_set)
-                    .dst_binding(1)
-                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-                    .buffer_info(from_ref(
-                        &vk::DescriptorBufferInfo::builder()
-                            .buffer(buffer_b.0)
-                            .offset(0)
-                            .range(vk::WHOLE_SIZE),
-                    ))
-                    .build(),
-                vk::WriteDescriptorSet::builder()
-                    .dst_set(descriptor_set)
-                    .dst_binding(2)
-                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
-                    .buffer_info(from_ref(
-                        &vk::DescriptorBufferInfo::builder()
-                            .buffer(buffer_c.0)
-                            .offset(0)
-                            .range(vk::WHOLE_SIZE),
-                    ))
-                    .build(),
-            ],
-            &[],
-        );
+        my_shader_set::MyDescriptorSet::writer()
+            .aa(buffer_a.0)
+            .bb(buffer_b.0)
+            .c(buffer_c.0)
+            .write(&device, descriptor_set)?;
     }
 
     let compute_shader = unsafe { my_shader_set::MyComputeShader::create_shader_module(&device)? };
 
-    let compute_pipeline_layout = unsafe {
-        device.create_pipeline_layout(
-            &vk::PipelineLayoutCreateInfo::builder().set_layouts(from_ref(&descriptor_set_layout)),
-            None,
-        )?
-    };
-
     let compute_pipeline = unsafe {
         device
             .create_compute_pipelines(
@@ -321,7 +225,8 @@ fn main() -> eyre::Result<()> {
             vk::PipelineBindPoint::COMPUTE,
             compute_pipeline,
         );
-        device.cmd_dispatch(command_buffer, 1, 1, 1);
+        let [x, y, z] = my_shader_set::MyComputeShader::group_count([256, 1, 1]);
+        device.cmd_dispatch(command_buffer, x, y, z);
 
         device.end_command_buffer(command_buffer)?;
     }
@@ -357,7 +262,9 @@ fn main() -> eyre::Result<()> {
         device.destroy_pipeline_layout(compute_pipeline_layout, None);
 
         device.destroy_descriptor_pool(descriptor_pool, None);
-        device.destroy_descriptor_set_layout(descriptor_set_layout, None);
+        for descriptor_set_layout in descriptor_set_layouts {
+            device.destroy_descriptor_set_layout(descriptor_set_layout, None);
+        }
 
         allocator.destroy_buffer(buffer_c.0, buffer_c.1);
         allocator.destroy_buffer(buffer_b.0, buffer_b.1);